@@ -1,19 +1,30 @@
+use std::env;
+use std::fmt::Write;
+
 use aoc2023::fetch_input;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let input = fetch_input(2).await?;
-    println!("PART 1: {}", part1(&input));
-    println!("PART 2: {}", part2(&input));
+    match env::args().nth(1).as_deref() {
+        Some("--report") => print!("{}", report_table(&input)),
+        Some("--csv") => print!("{}", report_csv(&input)),
+        _ => {
+            println!("PART 1: {}", part1(&input));
+            println!("PART 2: {}", part2(&input));
+        }
+    }
 
     Ok(())
 }
 
-// 12 red
-// 13 green
-// 14 blue
+const LIMIT: Rgb = Rgb {
+    red: 12,
+    green: 13,
+    blue: 14,
+};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
 struct Rgb {
     red: usize,
     green: usize,
@@ -22,7 +33,11 @@ struct Rgb {
 
 impl Rgb {
     fn legal(&self) -> bool {
-        self.red <= 12 && self.green <= 13 && self.blue <= 14
+        self.red <= LIMIT.red && self.green <= LIMIT.green && self.blue <= LIMIT.blue
+    }
+
+    fn power(&self) -> usize {
+        self.red * self.green * self.blue
     }
 
     fn add_color(&mut self, color: &str, count: usize) {
@@ -50,7 +65,7 @@ impl Rgb {
 fn game_subsets(line: &str) -> (usize, Vec<&str>) {
     let mut split = line.split(':');
     let game = game_num(split.next().unwrap());
-    let subsets = split.last().unwrap().split(';').collect::<Vec<_>>();
+    let subsets = split.next_back().unwrap().split(';').collect::<Vec<_>>();
     (game, subsets)
 }
 
@@ -82,11 +97,7 @@ fn part1(input: &str) -> usize {
 
     for game in input.lines() {
         let (game, subsets) = game_subsets(game);
-        if subsets
-            .iter()
-            .map(|subset| subset_colors(subset).legal())
-            .all(|legal| legal)
-        {
+        if subsets.iter().all(|subset| subset_colors(subset).legal()) {
             valid_games.push(game);
         }
     }
@@ -104,11 +115,95 @@ fn part2(input: &str) -> usize {
             rgb.count_max(&subset_colors(subset));
         }
 
-        ans += rgb.red * rgb.green * rgb.blue;
+        ans += rgb.power();
     }
     ans
 }
 
+#[derive(Debug)]
+struct GameReport<'a> {
+    game: usize,
+    min_bag: Rgb,
+    // 1-based index and text of the first draw over the limit
+    broken_by: Option<(usize, &'a str)>,
+}
+
+impl<'a> GameReport<'a> {
+    fn new(line: &'a str) -> Self {
+        let (game, subsets) = game_subsets(line);
+        let mut min_bag = Rgb::default();
+        let mut broken_by = None;
+
+        for (idx, subset) in subsets.into_iter().enumerate() {
+            let draw = subset_colors(subset);
+            if broken_by.is_none() && !draw.legal() {
+                broken_by = Some((idx + 1, subset.trim()));
+            }
+            min_bag.count_max(&draw);
+        }
+
+        Self {
+            game,
+            min_bag,
+            broken_by,
+        }
+    }
+
+    fn legal(&self) -> bool {
+        self.broken_by.is_none()
+    }
+}
+
+fn report_table(input: &str) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:>5} {:>5} {:>5} {:>5} {:>7}  {:<5}  broken by",
+        "game", "red", "green", "blue", "power", "legal"
+    )
+    .unwrap();
+
+    for report in input.lines().map(GameReport::new) {
+        let Rgb { red, green, blue } = report.min_bag;
+        let broken_by = report
+            .broken_by
+            .map_or(String::new(), |(idx, draw)| format!("draw {idx}: {draw}"));
+        writeln!(
+            out,
+            "{:>5} {red:>5} {green:>5} {blue:>5} {:>7}  {:<5}  {broken_by}",
+            report.game,
+            report.min_bag.power(),
+            report.legal(),
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+fn report_csv(input: &str) -> String {
+    let mut out = String::from("game,red,green,blue,power,legal,broken_draw,broken_draw_text\n");
+
+    for report in input.lines().map(GameReport::new) {
+        let Rgb { red, green, blue } = report.min_bag;
+        let (broken_idx, broken_draw) = report
+            .broken_by
+            .map_or((String::new(), String::new()), |(idx, draw)| {
+                (idx.to_string(), format!("\"{draw}\""))
+            });
+        writeln!(
+            out,
+            "{},{red},{green},{blue},{},{},{broken_idx},{broken_draw}",
+            report.game,
+            report.min_bag.power(),
+            report.legal(),
+        )
+        .unwrap();
+    }
+
+    out
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -140,4 +235,40 @@ Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
 Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
         assert_eq!(part2(input), 2286);
     }
+
+    #[test]
+    fn game_report() {
+        let report = GameReport::new(
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+        );
+        assert_eq!(report.game, 3);
+        assert_eq!(
+            report.min_bag,
+            Rgb {
+                red: 20,
+                green: 13,
+                blue: 6
+            }
+        );
+        assert_eq!(report.min_bag.power(), 1560);
+        assert_eq!(report.broken_by, Some((1, "8 green, 6 blue, 20 red")));
+        assert!(!report.legal());
+
+        let report = GameReport::new("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green");
+        assert_eq!(report.broken_by, None);
+        assert!(report.legal());
+    }
+
+    #[test]
+    fn report_csv_sample() {
+        let input = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red";
+        assert_eq!(
+            report_csv(input),
+            "game,red,green,blue,power,legal,broken_draw,broken_draw_text
+1,4,2,6,48,true,,
+4,14,3,15,630,false,3,\"3 green, 15 blue, 14 red\"
+"
+        );
+    }
 }
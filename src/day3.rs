@@ -1,28 +1,36 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
 use std::ops::Range;
 
+use anyhow::Context;
 use aoc2023::fetch_input;
+use aoc2023::grid::{Grid, Pos};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let input = fetch_input(3).await?;
-    println!("PART 1: {}", part1(&input));
-    println!("PART 2: {}", part2(&input));
+    match env::args().nth(1).as_deref() {
+        Some("--render") => {
+            let schematic = Schematic::new(&input)?;
+            print!("{}", render(&schematic, io::stdout().is_terminal()));
+        }
+        Some("--query") => print!("{}", query_report(&Schematic::new(&input)?)),
+        _ => {
+            println!("PART 1: {}", part1(&input)?);
+            println!("PART 2: {}", part2(&input)?);
+        }
+    }
 
     Ok(())
 }
 
-// row, col_rng -> num
-// symbol neighors (row, col)
-
-#[derive(Debug, PartialEq)]
-struct Pos(usize, usize); // row, col
-
 #[derive(Debug, PartialEq)]
 struct NumPos {
     row: usize,
     col_rng: Range<usize>,
     num: usize,
-    counted: bool,
 }
 
 impl NumPos {
@@ -32,15 +40,14 @@ impl NumPos {
             row,
             col_rng: col_start..col_start + num_str.len(),
             num: num_str.parse().unwrap(),
-            counted: false,
         }
     }
 }
 
-fn get_num_pos(schematic: &[Vec<char>]) -> Vec<NumPos> {
+fn get_num_pos(schematic: &Grid<char>) -> Vec<NumPos> {
     let mut nums = Vec::new();
 
-    for (row_num, row) in schematic.iter().enumerate() {
+    for (row_num, row) in schematic.iter_rows().enumerate() {
         let mut num_start = None;
         for (col_num, ch) in row.iter().enumerate() {
             match (ch.is_numeric(), num_start) {
@@ -60,140 +67,336 @@ fn get_num_pos(schematic: &[Vec<char>]) -> Vec<NumPos> {
     nums
 }
 
-fn symbol_pos(schematic: &[Vec<char>]) -> Vec<Vec<Pos>> {
-    let mut symbols = Vec::new();
+fn positions_of(schematic: &Grid<char>, is_target: impl Fn(char) -> bool) -> Vec<Pos> {
+    schematic
+        .iter()
+        .filter(|(_, &ch)| is_target(ch))
+        .map(|(pos, _)| pos)
+        .collect()
+}
 
-    let rows = schematic.len();
-    let cols = schematic.first().map_or(0, Vec::len);
+fn symbol_pos(schematic: &Grid<char>) -> Vec<Pos> {
+    positions_of(schematic, |ch| !ch.is_numeric() && ch != '.')
+}
 
-    for (row_num, row) in schematic.iter().enumerate() {
-        for (col_num, &ch) in row.iter().enumerate() {
-            if ch.is_numeric() || ch == '.' {
-                continue;
+fn gear_pos(schematic: &Grid<char>) -> Vec<Pos> {
+    positions_of(schematic, |ch| ch == '*')
+}
+
+fn create_schematic(input: &str) -> anyhow::Result<Grid<char>> {
+    input.parse().context("malformed schematic")
+}
+
+#[derive(Debug)]
+struct Schematic {
+    grid: Grid<char>,
+    nums: Vec<NumPos>,
+    // index into `nums` of the number covering each cell, if any
+    labels: Grid<Option<usize>>,
+}
+
+impl Schematic {
+    fn new(input: &str) -> anyhow::Result<Self> {
+        let grid = create_schematic(input)?;
+        let nums = get_num_pos(&grid);
+
+        let mut labels = Grid::new(grid.rows(), grid.cols(), None);
+        for (id, num) in nums.iter().enumerate() {
+            for col in num.col_rng.clone() {
+                labels[Pos::new(num.row, col)] = Some(id);
             }
-            let mut symbol_neighbors = Vec::new();
-            for dx in -1..=1 {
-                for dy in -1..=1 {
-                    let new_row = row_num as isize + dx;
-                    let new_col = col_num as isize + dy;
-
-                    if new_row >= 0
-                        && new_row < rows as isize
-                        && new_col >= 0
-                        && new_col < cols as isize
-                    {
-                        symbol_neighbors.push(Pos(new_row as usize, new_col as usize));
-                    }
-                }
+        }
+
+        Ok(Self { grid, nums, labels })
+    }
+
+    /// How many distinct symbols each number in `nums` touches.
+    fn symbol_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.nums.len()];
+        for symbol in symbol_pos(&self.grid) {
+            for id in self.adjacent_nums(symbol) {
+                counts[id] += 1;
             }
-            symbols.push(symbol_neighbors)
         }
+        counts
+    }
+
+    /// Whether each number in `nums` touches a symbol.
+    fn part_flags(&self) -> Vec<bool> {
+        self.symbol_counts()
+            .into_iter()
+            .map(|count| count > 0)
+            .collect()
+    }
+
+    /// Every `*` touching exactly two numbers, with the indices of those numbers. A number
+    /// may belong to several gears.
+    fn gears(&self) -> Vec<(Pos, usize, usize)> {
+        gear_pos(&self.grid)
+            .into_iter()
+            .filter_map(|gear| match self.adjacent_nums(gear)[..] {
+                [a, b] => Some((gear, a, b)),
+                _ => None,
+            })
+            .collect()
     }
 
-    symbols
+    /// Distinct indices into `nums` of the numbers touching `pos`, in ascending order.
+    fn adjacent_nums(&self, pos: Pos) -> Vec<usize> {
+        let mut ids = self
+            .labels
+            .neighbors8(pos)
+            .filter_map(|neighbor| self.labels[neighbor])
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
 }
 
-fn create_schematic(input: &str) -> Vec<Vec<char>> {
-    input
-        .lines()
-        .map(|line| line.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>()
+fn part1(input: &str) -> anyhow::Result<usize> {
+    let schematic = Schematic::new(input)?;
+
+    Ok(schematic
+        .nums
+        .iter()
+        .zip(schematic.part_flags())
+        .filter(|(_, is_part)| *is_part)
+        .map(|(num, _)| num.num)
+        .sum())
 }
 
-fn part1(input: &str) -> usize {
-    let schematic = create_schematic(input);
-    let mut nums = get_num_pos(&schematic);
-    let symbols = symbol_pos(&schematic);
+fn part2(input: &str) -> anyhow::Result<usize> {
+    let schematic = Schematic::new(input)?;
 
-    let mut sum = 0;
+    Ok(schematic
+        .gears()
+        .into_iter()
+        .map(|(_, a, b)| schematic.nums[a].num * schematic.nums[b].num)
+        .sum())
+}
 
-    for symbol_neighbors in &symbols {
-        for num in &mut nums {
-            if num.counted {
-                continue;
-            }
-            for sym_neighbor in symbol_neighbors {
-                if sym_neighbor.0 == num.row
-                    && num.col_rng.contains(&sym_neighbor.1)
-                    && !num.counted
-                {
-                    sum += num.num;
-                    num.counted = true;
-                }
-            }
+/// Part numbers touching each kind of symbol. A number next to two different symbols is
+/// listed under both, but only once per symbol kind.
+fn parts_by_symbol(schematic: &Schematic) -> BTreeMap<char, Vec<&NumPos>> {
+    let mut ids_by_symbol = BTreeMap::<char, Vec<usize>>::new();
+    for symbol in symbol_pos(&schematic.grid) {
+        ids_by_symbol
+            .entry(schematic.grid[symbol])
+            .or_default()
+            .extend(schematic.adjacent_nums(symbol));
+    }
+
+    ids_by_symbol
+        .into_iter()
+        .map(|(symbol, mut ids)| {
+            ids.sort_unstable();
+            ids.dedup();
+            let nums = ids.into_iter().map(|id| &schematic.nums[id]).collect();
+            (symbol, nums)
+        })
+        .collect()
+}
+
+/// Numbers that touch no symbol at all.
+fn orphan_nums(schematic: &Schematic) -> Vec<&NumPos> {
+    schematic
+        .nums
+        .iter()
+        .zip(schematic.part_flags())
+        .filter(|(_, is_part)| !is_part)
+        .map(|(num, _)| num)
+        .collect()
+}
+
+/// How many numbers touch more than one symbol.
+fn multi_symbol_count(schematic: &Schematic) -> usize {
+    schematic
+        .symbol_counts()
+        .into_iter()
+        .filter(|&count| count > 1)
+        .count()
+}
+
+fn query_report(schematic: &Schematic) -> String {
+    let join = |nums: &[&NumPos]| {
+        nums.iter()
+            .map(|num| num.num.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut out = String::new();
+    for (symbol, nums) in parts_by_symbol(schematic) {
+        writeln!(out, "{symbol}: {}", join(&nums)).unwrap();
+    }
+    writeln!(out, "orphans: {}", join(&orphan_nums(schematic))).unwrap();
+    writeln!(
+        out,
+        "multi-symbol numbers: {}",
+        multi_symbol_count(schematic)
+    )
+    .unwrap();
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Empty,
+    Part,
+    NonPart,
+    Symbol,
+    Gear,
+}
+
+impl Cell {
+    fn ansi(self) -> &'static str {
+        match self {
+            Cell::Empty => "\x1b[2m",
+            Cell::Part => "\x1b[32m",
+            Cell::NonPart => "\x1b[31m",
+            Cell::Symbol => "\x1b[33m",
+            Cell::Gear => "\x1b[1;35m",
+        }
+    }
+
+    fn marker(self) -> char {
+        match self {
+            Cell::Empty => ' ',
+            Cell::Part => 'P',
+            Cell::NonPart => 'n',
+            Cell::Symbol => 'S',
+            Cell::Gear => 'G',
         }
     }
-    sum
 }
 
-fn gear_pos(schematic: &[Vec<char>]) -> Vec<Vec<Pos>> {
-    let mut gears = Vec::new();
+fn classify(schematic: &Schematic) -> Grid<Cell> {
+    let is_part = schematic.part_flags();
+    let mut cells = schematic.labels.map(|label| match label {
+        Some(id) if is_part[*id] => Cell::Part,
+        Some(_) => Cell::NonPart,
+        None => Cell::Empty,
+    });
 
-    let rows = schematic.len();
-    let cols = schematic.first().map_or(0, Vec::len);
+    for symbol in symbol_pos(&schematic.grid) {
+        cells[symbol] = Cell::Symbol;
+    }
+    for (gear, _, _) in schematic.gears() {
+        cells[gear] = Cell::Gear;
+    }
 
-    for (row_num, row) in schematic.iter().enumerate() {
-        for (col_num, &ch) in row.iter().enumerate() {
-            if ch != '*' {
-                continue;
-            }
-            let mut gear_neighbors = Vec::new();
-            for dx in -1..=1 {
-                for dy in -1..=1 {
-                    let new_row = row_num as isize + dx;
-                    let new_col = col_num as isize + dy;
-
-                    if new_row >= 0
-                        && new_row < rows as isize
-                        && new_col >= 0
-                        && new_col < cols as isize
-                    {
-                        gear_neighbors.push(Pos(new_row as usize, new_col as usize));
-                    }
-                }
+    cells
+}
+
+/// Draw the schematic with every cell coloured by what it is. Without colour, each row is
+/// followed by a line of markers: `P` part number, `n` non-part number, `S` symbol, `G` gear.
+fn render(schematic: &Schematic, color: bool) -> String {
+    let cells = classify(schematic);
+    let mut out = String::new();
+
+    for (row, kinds) in schematic.grid.iter_rows().zip(cells.iter_rows()) {
+        if color {
+            for (ch, kind) in row.iter().zip(kinds) {
+                write!(out, "{}{ch}\x1b[0m", kind.ansi()).unwrap();
             }
-            gears.push(gear_neighbors)
+            out.push('\n');
+        } else {
+            out.extend(row);
+            out.push('\n');
+            let markers = kinds.iter().map(|kind| kind.marker()).collect::<String>();
+            writeln!(out, "{}", markers.trim_end()).unwrap();
         }
     }
 
-    gears
+    out
 }
 
-fn part2(input: &str) -> usize {
-    let schematic = create_schematic(input);
-    let mut nums = get_num_pos(&schematic);
-    let gears = gear_pos(&schematic);
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use aoc2023::rng::Rng;
 
-    let mut sum = 0;
+    use super::*;
 
-    for gear_neighbors in &gears {
-        let mut gear = None;
-        for num in &mut nums {
-            if num.counted {
-                continue;
+    fn generate_schematic(rows: usize, cols: usize, seed: u64) -> String {
+        let mut rng = Rng::new(seed);
+        let mut out = String::with_capacity(rows * (cols + 1));
+        for _ in 0..rows {
+            let mut col = 0;
+            while col < cols {
+                match rng.range(0..10) {
+                    0..=5 => out.push('.'),
+                    6 => out.push(*rng.pick(&['*', '#', '$', '+', '/', '@', '=', '%', '-', '&'])),
+                    _ => {
+                        // keep a trailing '.' so neighbouring numbers don't merge
+                        let len = (rng.range(1..4) as usize).min(cols - col - 1);
+                        for _ in 0..len {
+                            out.push(char::from(b'0' + rng.range(0..10) as u8));
+                        }
+                        out.push('.');
+                        col += len;
+                    }
+                }
+                col += 1;
             }
-            for gear_neighbor in gear_neighbors {
-                if gear_neighbor.0 == num.row
-                    && num.col_rng.contains(&gear_neighbor.1)
-                    && !num.counted
-                {
-                    if let Some(tooth_count) = gear {
-                        sum += tooth_count * num.num;
-                    } else {
-                        gear = Some(num.num);
+            out.push('\n');
+        }
+        out
+    }
+
+    // the original part 1: every symbol against every number against every neighbour
+    fn part1_quadratic(input: &str) -> usize {
+        let grid = create_schematic(input).unwrap();
+        let nums = get_num_pos(&grid);
+        let mut counted = vec![false; nums.len()];
+        let mut sum = 0;
+
+        for symbol in symbol_pos(&grid) {
+            let neighbors = grid.neighbors8(symbol).collect::<Vec<_>>();
+            for (num, counted) in nums.iter().zip(&mut counted) {
+                for neighbor in &neighbors {
+                    if !*counted && neighbor.row == num.row && num.col_rng.contains(&neighbor.col) {
+                        sum += num.num;
+                        *counted = true;
                     }
-                    num.counted = true;
                 }
             }
         }
+        sum
     }
 
-    sum
-}
+    #[test]
+    #[ignore = "benchmark: cargo test --release --bin day3 -- --ignored --nocapture"]
+    fn bench_1000x1000() {
+        // the quadratic scan grows with the fourth power of the side, so compare on smaller grids
+        for side in [100, 200, 400] {
+            let input = generate_schematic(side, side, 3);
+
+            let start = Instant::now();
+            let old = part1_quadratic(&input);
+            let old_time = start.elapsed();
+
+            let start = Instant::now();
+            let new = part1(&input).unwrap();
+            println!(
+                "{side}x{side}: quadratic {old_time:?}, label grid {:?}",
+                start.elapsed()
+            );
+            assert_eq!(old, new);
+        }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+        let input = generate_schematic(1000, 1000, 3);
+
+        let start = Instant::now();
+        let sum = part1(&input).unwrap();
+        println!("part1 = {sum} in {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let sum = part2(&input).unwrap();
+        println!("part2 = {sum} in {:?}", start.elapsed());
+    }
 
     #[test]
     fn part1_sample() {
@@ -209,7 +412,7 @@ mod test {
 ...$.*....
 .664.598.."
             .trim();
-        assert_eq!(part1(input), 4361);
+        assert_eq!(part1(input).unwrap(), 4361);
     }
 
     #[test]
@@ -220,7 +423,7 @@ mod test {
 ......*.
 "
         .trim();
-        assert_eq!(part1(input), 4);
+        assert_eq!(part1(input).unwrap(), 4);
     }
 
     #[test]
@@ -231,7 +434,7 @@ mod test {
 ......*.
 "
         .trim();
-        assert_eq!(part1(input), 28);
+        assert_eq!(part1(input).unwrap(), 28);
     }
     #[test]
     fn part1_3() {
@@ -241,7 +444,7 @@ mod test {
 11....11
 "
         .trim();
-        assert_eq!(part1(input), 44);
+        assert_eq!(part1(input).unwrap(), 44);
     }
     #[test]
     fn part1_4() {
@@ -252,7 +455,7 @@ $......$
 $......$
 "
         .trim();
-        assert_eq!(part1(input), 4);
+        assert_eq!(part1(input).unwrap(), 4);
     }
     #[test]
     fn part1_5() {
@@ -263,7 +466,7 @@ $......$
 $......$
 "
         .trim();
-        assert_eq!(part1(input), 44);
+        assert_eq!(part1(input).unwrap(), 44);
     }
     #[test]
     fn part1_6() {
@@ -274,7 +477,7 @@ $11
 ...
 "
         .trim();
-        assert_eq!(part1(input), 22);
+        assert_eq!(part1(input).unwrap(), 22);
     }
     #[test]
     fn part1_7() {
@@ -289,7 +492,7 @@ $..
 ..$
 "
         .trim();
-        assert_eq!(part1(input), 44);
+        assert_eq!(part1(input).unwrap(), 44);
     }
     #[test]
     fn part1_8() {
@@ -297,7 +500,7 @@ $..
 11.$.
 "
         .trim();
-        assert_eq!(part1(input), 0);
+        assert_eq!(part1(input).unwrap(), 0);
     }
 
     #[test]
@@ -315,6 +518,128 @@ $..
 .664.598..
 "
         .trim();
-        assert_eq!(part2(input), 467835);
+        assert_eq!(part2(input).unwrap(), 467835);
+    }
+
+    #[test]
+    fn part2_three_neighbors() {
+        let input = "
+2.3
+.*.
+4..
+"
+        .trim();
+        assert_eq!(part2(input).unwrap(), 0);
+    }
+
+    #[test]
+    fn part2_shared_number() {
+        let input = "
+2.....
+.*.*..
+..5.7.
+"
+        .trim();
+        assert_eq!(part2(input).unwrap(), 2 * 5 + 5 * 7);
+    }
+
+    #[test]
+    fn part2_same_number_twice() {
+        let input = "
+123.
+.*..
+"
+        .trim();
+        assert_eq!(part2(input).unwrap(), 0);
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let input = "467..114..\r\n...*......\r\n..35..633.\r\n";
+        assert_eq!(part1(input).unwrap(), 467 + 35);
+        assert_eq!(part2(input).unwrap(), 467 * 35);
+    }
+
+    #[test]
+    fn ragged_rows() {
+        let input = "467..114..\n...*......\n..35..6";
+        let err = part1(input).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "malformed schematic: row 3 has width 7, expected 10"
+        );
+    }
+
+    #[test]
+    fn render_markers() {
+        let input = "
+467..114..
+...*......
+..35..633.
+..........
+......#..*
+"
+        .trim();
+        let schematic = Schematic::new(input).unwrap();
+        assert_eq!(
+            render(&schematic, false),
+            "\
+467..114..
+PPP  nnn
+...*......
+   G
+..35..633.
+  PP  nnn
+..........
+
+......#..*
+      S  S
+"
+        );
+    }
+
+    #[test]
+    fn render_color() {
+        let schematic = Schematic::new("1*2").unwrap();
+        assert_eq!(
+            render(&schematic, true),
+            "\x1b[32m1\x1b[0m\x1b[1;35m*\x1b[0m\x1b[32m2\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn queries() {
+        let input = "
+467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..
+"
+        .trim();
+        let schematic = Schematic::new(input).unwrap();
+        assert_eq!(
+            query_report(&schematic),
+            "\
+#: 633
+$: 664
+*: 467 35 617 755 598
++: 592
+orphans: 114 58
+multi-symbol numbers: 0
+"
+        );
+
+        let schematic = Schematic::new("$12*\n.3..").unwrap();
+        let by_symbol = parts_by_symbol(&schematic);
+        assert_eq!(by_symbol[&'$'].len(), 2);
+        assert_eq!(by_symbol[&'*'].len(), 1);
+        assert_eq!(multi_symbol_count(&schematic), 1);
+        assert!(orphan_nums(&schematic).is_empty());
     }
 }
@@ -1,93 +1,346 @@
+use std::env;
+use std::fmt::{Display, Write};
+use std::ops::Range;
+
+use anyhow::{ensure, Context};
 use aoc2023::fetch_input;
+use num_bigint::BigUint;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let input = fetch_input(4).await?;
-    println!("PART 1: {}", part1(&input));
-    println!("PART 2: {}", part2(&input));
+    match env::args().nth(1).as_deref() {
+        Some("--trace") => print!("{}", trace_table(&trace(&Card::parse_cards(&input)?)?)),
+        Some("--trace-json") => print!("{}", trace_json(&trace(&Card::parse_cards(&input)?)?)),
+        Some("--bigint") => {
+            let cards = Card::parse_cards(&input)?;
+            println!("PART 1: {}", total_points::<BigUint>(&cards)?);
+            println!("PART 2: {}", total_cards::<BigUint>(&cards)?);
+        }
+        _ => {
+            println!("PART 1: {}", part1(&input)?);
+            println!("PART 2: {}", part2(&input)?);
+        }
+    }
 
     Ok(())
 }
 
+// card numbers are all below this, so a card's numbers fit in a `u128` bitset
+const MAX_NUM: usize = 128;
+
 #[derive(Debug)]
 struct Card {
-    // card_num: usize,
-    winning_nums: Vec<usize>,
-    available_nums: Vec<usize>,
+    card_num: usize,
+    winning_nums: u128,
+    available_nums: u128,
 }
 
 impl Card {
-    fn parse_cards(input: &str) -> Vec<Self> {
+    /// Parse every card, checking they are numbered 1..=n in order with none missing.
+    fn parse_cards(input: &str) -> anyhow::Result<Vec<Self>> {
         let mut cards = Vec::new();
-        for line in input.lines() {
-            let mut split = line.split(':');
-            let _ = split.next();
-
-            let mut num_split = split.next().unwrap().split('|');
-
-            let winning_nums_str = num_split.next().unwrap();
-            let winning_nums = winning_nums_str
-                .split_whitespace()
-                .map(|num| num.trim().parse::<usize>().unwrap())
-                .collect::<Vec<_>>();
-
-            let available_nums_str = num_split.next().unwrap();
-            let available_nums = available_nums_str
-                .split_whitespace()
-                .map(|num| num.trim().parse::<usize>().unwrap())
-                .collect::<Vec<_>>();
-
-            cards.push(Self {
-                winning_nums,
-                available_nums,
-            });
+        for (idx, line) in input.lines().enumerate() {
+            let card = Self::parse_card(line).with_context(|| format!("line {}", idx + 1))?;
+            ensure!(
+                card.card_num == idx + 1,
+                "line {}: expected card {}, found card {}",
+                idx + 1,
+                idx + 1,
+                card.card_num
+            );
+            cards.push(card);
         }
 
-        cards
+        Ok(cards)
     }
 
-    fn winning_numbers(&self) -> Vec<usize> {
-        self.winning_nums
-            .iter()
-            .filter(|num| self.available_nums.contains(num))
-            .cloned()
-            .collect()
+    fn parse_card(line: &str) -> anyhow::Result<Self> {
+        let (label, nums) = line
+            .split_once(':')
+            .context("missing `:` after card label")?;
+        let card_num = label
+            .strip_prefix("Card")
+            .context("expected `Card N:` label")?
+            .trim()
+            .parse::<usize>()
+            .context("invalid card number")?;
+
+        let (winning_nums, available_nums) = nums.split_once('|').context("missing `|`")?;
+
+        Ok(Self {
+            card_num,
+            winning_nums: Self::parse_nums(winning_nums)?,
+            available_nums: Self::parse_nums(available_nums)?,
+        })
+    }
+
+    fn parse_nums(nums: &str) -> anyhow::Result<u128> {
+        nums.split_whitespace().try_fold(0, |set, num| {
+            let num = num.parse::<usize>()?;
+            ensure!(num < MAX_NUM, "card number {num} is not below {MAX_NUM}");
+            Ok(set | 1 << num)
+        })
+    }
+
+    fn match_count(&self) -> usize {
+        (self.winning_nums & self.available_nums).count_ones() as usize
+    }
+}
+
+/// Integer type used for scores and copy counts, which grow exponentially with the matches.
+trait Count: Sized + Clone + Display {
+    const NAME: &'static str;
+
+    fn zero() -> Self;
+    fn one() -> Self;
+    /// `2^exp`, or `None` if it doesn't fit.
+    fn pow2(exp: u32) -> Option<Self>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+}
+
+impl Count for u128 {
+    const NAME: &'static str = "u128";
+
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+
+    fn pow2(exp: u32) -> Option<Self> {
+        1_u128.checked_shl(exp)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u128::checked_add(*self, *other)
+    }
+}
+
+impl Count for BigUint {
+    const NAME: &'static str = "BigUint";
+
+    fn zero() -> Self {
+        BigUint::ZERO
     }
+
+    fn one() -> Self {
+        BigUint::from(1_u8)
+    }
+
+    fn pow2(exp: u32) -> Option<Self> {
+        Some(Self::one() << exp)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+}
+
+fn total_points<T: Count>(cards: &[Card]) -> anyhow::Result<T> {
+    let mut total = T::zero();
+    for card in cards {
+        let matches = card.match_count() as u32;
+        if matches == 0 {
+            continue;
+        }
+        let score = T::pow2(matches - 1)
+            .with_context(|| format!("card {}: score overflows {}", card.card_num, T::NAME))?;
+        total = total
+            .checked_add(&score)
+            .with_context(|| format!("total points overflow {}", T::NAME))?;
+    }
+    Ok(total)
 }
 
-fn part1(input: &str) -> usize {
-    let cards = Card::parse_cards(input);
-    cards
+fn total_cards<T: Count>(cards: &[Card]) -> anyhow::Result<T> {
+    let mut card_copies = vec![T::one(); cards.len()];
+
+    for (i, card) in cards.iter().enumerate() {
+        for j in won_cards(i, card.match_count(), cards.len()) {
+            card_copies[j] = card_copies[j]
+                .checked_add(&card_copies[i])
+                .with_context(|| {
+                    format!("card {}: copies overflow {}", cards[j].card_num, T::NAME)
+                })?;
+        }
+    }
+
+    card_copies
         .iter()
-        .filter_map(|card| {
-            let win_count = card.winning_numbers().len() as i32;
-            if win_count > 0 {
-                Some(2_i32.pow((win_count - 1_i32) as u32) as usize)
-            } else {
-                None
-            }
-        })
-        .sum()
+        .try_fold(T::zero(), |total, copies| total.checked_add(copies))
+        .with_context(|| format!("total cards overflow {}", T::NAME))
+}
+
+fn part1(input: &str) -> anyhow::Result<u128> {
+    total_points(&Card::parse_cards(input)?)
+}
+
+fn part2(input: &str) -> anyhow::Result<u128> {
+    total_cards(&Card::parse_cards(input)?)
+}
+
+/// Indices of the cards won by the card at index `i`: the next `wins` cards, stopping at the
+/// end of the table.
+fn won_cards(i: usize, wins: usize, len: usize) -> Range<usize> {
+    i + 1..std::cmp::min(i + 1 + wins, len)
+}
+
+#[derive(Debug, PartialEq)]
+struct CardTrace {
+    card_num: usize,
+    matches: usize,
+    copies: u128,
+    // (card_num, copies) handed over by each earlier card that won this one
+    received: Vec<(usize, u128)>,
+    running_total: u128,
 }
 
-fn part2(input: &str) -> usize {
-    let cards = Card::parse_cards(input);
-    let mut card_copies = vec![1; cards.len()];
+fn trace(cards: &[Card]) -> anyhow::Result<Vec<CardTrace>> {
+    let mut pending = vec![Vec::new(); cards.len()];
+    let mut traces = Vec::with_capacity(cards.len());
+    let mut running_total = 0_u128;
 
     for (i, card) in cards.iter().enumerate() {
-        let wins = card.winning_numbers().len();
-        for j in i + 1..std::cmp::min(i + 1 + wins, cards.len()) {
-            card_copies[j] += card_copies[i];
+        let received = std::mem::take(&mut pending[i]);
+        let copies = received
+            .iter()
+            .try_fold(1_u128, |copies, (_, won)| copies.checked_add(*won))
+            .with_context(|| format!("card {}: copies overflow u128", card.card_num))?;
+        running_total = running_total
+            .checked_add(copies)
+            .with_context(|| format!("card {}: total cards overflow u128", card.card_num))?;
+
+        let matches = card.match_count();
+        for j in won_cards(i, matches, cards.len()) {
+            pending[j].push((card.card_num, copies));
         }
+
+        traces.push(CardTrace {
+            card_num: card.card_num,
+            matches,
+            copies,
+            received,
+            running_total,
+        });
+    }
+
+    Ok(traces)
+}
+
+fn trace_table(traces: &[CardTrace]) -> String {
+    let mut out = format!(
+        "{:>6} {:>7} {:>8} {:>13}  received from\n",
+        "card", "matches", "copies", "running total"
+    );
+    for trace in traces {
+        let received = trace
+            .received
+            .iter()
+            .map(|(card_num, copies)| format!("{card_num}(+{copies})"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(
+            out,
+            "{:>6} {:>7} {:>8} {:>13}  {received}",
+            trace.card_num, trace.matches, trace.copies, trace.running_total
+        )
+        .unwrap();
     }
+    out
+}
 
-    card_copies.iter().sum()
+fn trace_json(traces: &[CardTrace]) -> String {
+    let mut out = String::from("[\n");
+    for (idx, trace) in traces.iter().enumerate() {
+        let received = trace
+            .received
+            .iter()
+            .map(|(card_num, copies)| format!(r#"{{"card":{card_num},"copies":{copies}}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let sep = if idx + 1 < traces.len() { "," } else { "" };
+        writeln!(
+            out,
+            r#"  {{"card":{},"matches":{},"copies":{},"received_from":[{received}],"running_total":{}}}{sep}"#,
+            trace.card_num, trace.matches, trace.copies, trace.running_total
+        )
+        .unwrap();
+    }
+    out.push_str("]\n");
+    out
 }
 
 #[cfg(test)]
 mod test {
+    use std::time::Instant;
+
+    use aoc2023::rng::Rng;
+
     use super::*;
 
+    fn generate_cards(count: usize, seed: u64) -> String {
+        let mut rng = Rng::new(seed);
+        let mut out = String::new();
+        for card in 1..=count {
+            // numbers within one list are distinct, like the real input
+            let mut nums = |n| {
+                let mut picked = Vec::new();
+                while picked.len() < n {
+                    let num = rng.range(1..100);
+                    if !picked.contains(&num) {
+                        picked.push(num);
+                    }
+                }
+                picked
+                    .iter()
+                    .map(|num| format!("{num:>2}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let winning = nums(10);
+            let available = nums(25);
+            out.push_str(&format!("Card {card:>6}: {winning} | {available}\n"));
+        }
+        out
+    }
+
+    #[test]
+    #[ignore = "benchmark: cargo test --release --bin day4 -- --ignored --nocapture"]
+    fn bench_match_count() {
+        let input = generate_cards(200_000, 4);
+        let cards = Card::parse_cards(&input).unwrap();
+
+        // the old representation: a linear `contains` per winning number
+        let vecs = input
+            .lines()
+            .map(|line| {
+                let (winning, available) = line.split_once(':').unwrap().1.split_once('|').unwrap();
+                let parse = |nums: &str| {
+                    nums.split_whitespace()
+                        .map(|num| num.parse::<usize>().unwrap())
+                        .collect::<Vec<_>>()
+                };
+                (parse(winning), parse(available))
+            })
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let total = vecs
+            .iter()
+            .map(|(winning, available)| {
+                winning.iter().filter(|num| available.contains(num)).count()
+            })
+            .sum::<usize>();
+        println!("vec contains: {total} matches in {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let total = cards.iter().map(Card::match_count).sum::<usize>();
+        println!("bitset:       {total} matches in {:?}", start.elapsed());
+    }
+
     #[test]
     fn part1_sample() {
         let input = "
@@ -100,7 +353,7 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
 "
         .trim();
 
-        assert_eq!(part1(input), 13);
+        assert_eq!(part1(input).unwrap(), 13);
     }
 
     #[test]
@@ -114,6 +367,143 @@ Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
 Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
 "
         .trim();
-        assert_eq!(part2(input), 30);
+        assert_eq!(part2(input).unwrap(), 30);
+    }
+
+    #[test]
+    fn card_nums() {
+        let input = "
+Card 1: 41 48 | 83 41
+Card 2: 13 32 | 61 30
+"
+        .trim();
+        let cards = Card::parse_cards(input).unwrap();
+        assert_eq!(cards[0].card_num, 1);
+        assert_eq!(cards[1].card_num, 2);
+        assert_eq!(cards[0].match_count(), 1);
+    }
+
+    #[test]
+    fn card_order() {
+        let reordered = "
+Card 2: 13 32 | 61 30
+Card 1: 41 48 | 83 41
+"
+        .trim();
+        let err = part2(reordered).unwrap_err();
+        assert_eq!(err.to_string(), "line 1: expected card 1, found card 2");
+
+        let missing = "
+Card 1: 41 48 | 83 41
+Card 3: 13 32 | 61 30
+"
+        .trim();
+        let err = part2(missing).unwrap_err();
+        assert_eq!(err.to_string(), "line 2: expected card 2, found card 3");
+
+        let unlabelled = "41 48 | 83 41";
+        let err = part1(unlabelled).unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 1: missing `:` after card label");
+    }
+
+    #[test]
+    fn trace_sample() {
+        let input = "
+Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+"
+        .trim();
+        let traces = trace(&Card::parse_cards(input).unwrap()).unwrap();
+        assert_eq!(
+            traces[3],
+            CardTrace {
+                card_num: 4,
+                matches: 1,
+                copies: 8,
+                received: vec![(1, 1), (2, 2), (3, 4)],
+                running_total: 15,
+            }
+        );
+        assert_eq!(traces.last().unwrap().running_total, part2(input).unwrap());
+
+        let table = trace_table(&traces);
+        assert_eq!(
+            table.lines().nth(4).unwrap(),
+            "     4       1        8            15  1(+1) 2(+2) 3(+4)"
+        );
+    }
+
+    #[test]
+    fn trace_json_format() {
+        let input = "
+Card 1: 41 48 | 41 48
+Card 2: 13 32 | 61 30
+"
+        .trim();
+        let traces = trace(&Card::parse_cards(input).unwrap()).unwrap();
+        assert_eq!(
+            trace_json(&traces),
+            r#"[
+  {"card":1,"matches":2,"copies":1,"received_from":[],"running_total":1},
+  {"card":2,"matches":0,"copies":2,"received_from":[{"card":1,"copies":1}],"running_total":3}
+]
+"#
+        );
+    }
+
+    // every card matches `matches` numbers, so copies roughly double per card
+    fn cascade(cards: usize, matches: usize) -> String {
+        let nums = (0..matches)
+            .map(|num| num.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        (1..=cards)
+            .map(|card| format!("Card {card}: {nums} | {nums}\n"))
+            .collect()
+    }
+
+    #[test]
+    fn points_overflow() {
+        let input = cascade(2, MAX_NUM);
+        let err = part1(&input).unwrap_err();
+        assert_eq!(err.to_string(), "total points overflow u128");
+
+        let cards = Card::parse_cards(&input).unwrap();
+        let total = total_points::<BigUint>(&cards).unwrap();
+        assert_eq!(total, BigUint::one() << 128);
+    }
+
+    #[test]
+    fn copies_overflow() {
+        let input = cascade(100, 10);
+        let cards = Card::parse_cards(&input).unwrap();
+        let small = total_cards::<u128>(&cards).unwrap();
+        assert_eq!(
+            total_cards::<BigUint>(&cards).unwrap(),
+            BigUint::from(small)
+        );
+
+        let input = cascade(200, 10);
+        let err = part2(&input).unwrap_err();
+        assert!(err.to_string().ends_with("copies overflow u128"), "{err}");
+        assert!(trace(&Card::parse_cards(&input).unwrap()).is_err());
+
+        let cards = Card::parse_cards(&input).unwrap();
+        let total = total_cards::<BigUint>(&cards).unwrap();
+        assert!(total > BigUint::from(u128::MAX));
+
+        // the first cascade too big for the trace runs out on the running total, not on a
+        // card's own copies
+        let err = (100..200)
+            .find_map(|len| trace(&Card::parse_cards(&cascade(len, 10)).unwrap()).err())
+            .unwrap();
+        assert!(
+            err.to_string().ends_with("total cards overflow u128"),
+            "{err}"
+        );
     }
 }
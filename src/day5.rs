@@ -1,17 +1,47 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
 use std::ops::Range;
-use std::str::Split;
 
+use anyhow::{bail, ensure, Context};
 use aoc2023::fetch_input;
+use aoc2023::interval::{IntervalSet, Offset};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let input = fetch_input(5).await?;
-    println!("PART 1: {}", part1(&input));
-    println!("PART 2: {}", part2(&input));
+    match env::args().nth(1).as_deref() {
+        Some("--seed-for") => {
+            let location = env::args()
+                .nth(2)
+                .context("expected a location")?
+                .parse::<usize>()?;
+            let almanac: Almanac<Part1Seeds> = Almanac::new(&input)?;
+            let table = almanac.collapse("seed", "location")?;
+            for seed in table.sources_of(location)? {
+                println!("{seed}");
+            }
+        }
+        Some("--check-gaps") => {
+            let almanac: Almanac<Part1Seeds> = Almanac::new(&input)?;
+            print_warnings(&almanac.warnings);
+        }
+        _ => {
+            let almanac: Almanac<Part1Seeds> = Almanac::new(&input)?;
+            print_warnings(&almanac.warnings);
+            println!("PART 1: {}", part1(&input)?);
+            println!("PART 2: {}", part2(&input)?);
+        }
+    }
 
     Ok(())
 }
 
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+}
+
 #[derive(Debug)]
 struct Map {
     dest_rng: Range<usize>,
@@ -19,58 +49,273 @@ struct Map {
 }
 
 impl Map {
-    fn new(dest_rng_start: usize, src_rng_start: usize, rng_len: usize) -> Self {
-        Self {
-            dest_rng: dest_rng_start..dest_rng_start + rng_len,
-            src_rng: src_rng_start..src_rng_start + rng_len,
-        }
+    fn new(dest_rng_start: usize, src_rng_start: usize, rng_len: usize) -> anyhow::Result<Self> {
+        let end = |start: usize| {
+            start
+                .checked_add(rng_len)
+                .with_context(|| format!("range {start}+{rng_len} overflows usize"))
+        };
+        Ok(Self {
+            dest_rng: dest_rng_start..end(dest_rng_start)?,
+            src_rng: src_rng_start..end(src_rng_start)?,
+        })
     }
 
-    fn dest_num(&self, src_num: usize) -> usize {
-        if self.src_rng.contains(&src_num) {
-            let offset = src_num - self.src_rng.start;
-            self.dest_rng.start + offset
-        } else {
-            src_num
-        }
+    /// How far this map moves each number in its source range.
+    fn offset(&self) -> Offset {
+        Offset::between(self.src_rng.start, self.dest_rng.start)
     }
 }
 
 trait FindInMaps {
-    fn find_map(&self, src_num: usize) -> usize;
+    fn map_ranges(&self, src: IntervalSet) -> anyhow::Result<IntervalSet>;
 }
 
 impl FindInMaps for Vec<Map> {
-    fn find_map(&self, src_num: usize) -> usize {
+    /// Push a whole set of numbers through the maps, splitting it wherever a map's source range
+    /// starts or ends. `Stage::validate` rejects overlapping source ranges, so each number is
+    /// moved by at most one map.
+    fn map_ranges(&self, src: IntervalSet) -> anyhow::Result<IntervalSet> {
+        let mut unmapped = src;
+        let mut mapped = IntervalSet::new();
+
         for map in self.iter() {
-            if map.src_rng.contains(&src_num) {
-                return map.dest_num(src_num);
+            let (inside, rest) = unmapped.split(&map.src_rng.clone().into());
+            let moved = inside
+                .checked_shift(map.offset())
+                .with_context(|| format!("{map:?} moves numbers past usize"))?;
+            mapped = mapped.union(&moved);
+            unmapped = rest;
+        }
+
+        Ok(mapped.union(&unmapped))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Piece {
+    src: Range<usize>,
+    offset: Offset,
+}
+
+impl Piece {
+    fn dest(&self) -> anyhow::Result<Range<usize>> {
+        shift(&self.src, self.offset)
+    }
+}
+
+fn shift_num(num: usize, offset: Offset) -> anyhow::Result<usize> {
+    offset
+        .apply(num)
+        .with_context(|| format!("shifting {num} by {offset:?} leaves usize"))
+}
+
+fn shift(rng: &Range<usize>, offset: Offset) -> anyhow::Result<Range<usize>> {
+    Ok(shift_num(rng.start, offset)?..shift_num(rng.end, offset)?)
+}
+
+/// One or more stages flattened into disjoint pieces, sorted by source and covering every
+/// number, so a lookup is a binary search.
+#[derive(Debug, Clone, PartialEq)]
+struct PiecewiseMap {
+    pieces: Vec<Piece>,
+}
+
+impl PiecewiseMap {
+    fn from_maps(maps: &[Map]) -> Self {
+        let mut claimed = IntervalSet::new();
+        let mut pieces = Vec::new();
+
+        // validated maps never overlap, so subtracting what's claimed only drops empty ranges
+        for map in maps {
+            let fresh = IntervalSet::from(map.src_rng.clone()).difference(&claimed);
+            pieces.extend(fresh.iter().map(|src| Piece {
+                src: src.clone(),
+                offset: map.offset(),
+            }));
+            claimed = claimed.union(&fresh);
+        }
+
+        let gaps = IntervalSet::from(0..usize::MAX).difference(&claimed);
+        pieces.extend(gaps.iter().map(|src| Piece {
+            src: src.clone(),
+            offset: Offset::ZERO,
+        }));
+
+        Self::from_pieces(pieces)
+    }
+
+    // sort disjoint pieces and merge neighbours that move numbers by the same amount
+    fn from_pieces(mut pieces: Vec<Piece>) -> Self {
+        pieces.sort_unstable_by_key(|piece| piece.src.start);
+
+        let mut merged: Vec<Piece> = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            match merged.last_mut() {
+                Some(last) if last.src.end == piece.src.start && last.offset == piece.offset => {
+                    last.src.end = piece.src.end
+                }
+                _ => merged.push(piece),
+            }
+        }
+        Self { pieces: merged }
+    }
+
+    /// The map equivalent to applying `self` and then `then`.
+    fn compose(&self, then: &Self) -> anyhow::Result<Self> {
+        let mut pieces = Vec::new();
+
+        for piece in &self.pieces {
+            let dest = piece.dest()?;
+            let mut idx = then.piece_idx(dest.start);
+            while let Some(next) = then
+                .pieces
+                .get(idx)
+                .filter(|next| next.src.start < dest.end)
+            {
+                let overlap = dest.start.max(next.src.start)..dest.end.min(next.src.end);
+                let offset = piece.offset.checked_add(next.offset).with_context(|| {
+                    format!("{:?} then {:?} overflows usize", piece.offset, next.offset)
+                })?;
+                pieces.push(Piece {
+                    src: shift(&overlap, -piece.offset)?,
+                    offset,
+                });
+                idx += 1;
+            }
+        }
+
+        Ok(Self::from_pieces(pieces))
+    }
+
+    fn piece_idx(&self, num: usize) -> usize {
+        self.pieces.partition_point(|piece| piece.src.end <= num)
+    }
+
+    fn lookup(&self, num: usize) -> anyhow::Result<usize> {
+        self.pieces
+            .get(self.piece_idx(num))
+            .map_or(Ok(num), |piece| shift_num(num, piece.offset))
+    }
+
+    /// Every number that maps to `dest`, in ascending order.
+    fn sources_of(&self, dest: usize) -> anyhow::Result<Vec<usize>> {
+        let mut sources = Vec::new();
+        for piece in &self.pieces {
+            if piece.dest()?.contains(&dest) {
+                sources.push(shift_num(dest, -piece.offset)?);
             }
         }
-        src_num
+        Ok(sources)
     }
 }
 
-trait Seeds {
-    fn new(seed_line: &str) -> Self;
-    fn iter(&self) -> std::slice::Iter<'_, usize>;
+trait Seeds: Sized {
+    fn new(seed_line: &str) -> anyhow::Result<Self>;
+    fn ranges(&self) -> IntervalSet;
+}
+
+fn parse_seed_nums(seed_line: &str) -> anyhow::Result<Vec<usize>> {
+    let nums = seed_line
+        .strip_prefix("seeds:")
+        .with_context(|| format!("expected `seeds:` at the start of `{seed_line}`"))?;
+    nums.split_whitespace()
+        .map(|num| {
+            num.parse()
+                .with_context(|| format!("invalid seed number `{num}`"))
+        })
+        .collect()
 }
 
 struct Part1Seeds(Vec<usize>);
 
 impl Seeds for Part1Seeds {
-    fn new(seed_line: &str) -> Self {
-        Self(
-            seed_line
-                .split_whitespace()
-                .skip(1)
-                .map(|num| num.parse::<usize>().unwrap())
-                .collect::<Vec<_>>(),
-        )
+    fn new(seed_line: &str) -> anyhow::Result<Self> {
+        Ok(Self(parse_seed_nums(seed_line)?))
+    }
+
+    fn ranges(&self) -> IntervalSet {
+        self.0.iter().map(|&seed| seed..seed + 1).collect()
+    }
+}
+
+/// The seed line read as `start len` pairs.
+struct Part2Seeds(Vec<Range<usize>>);
+
+impl Seeds for Part2Seeds {
+    fn new(seed_line: &str) -> anyhow::Result<Self> {
+        let nums = parse_seed_nums(seed_line)?;
+        ensure!(
+            nums.len() % 2 == 0,
+            "expected `start len` pairs, found {} seed numbers",
+            nums.len()
+        );
+        nums.chunks_exact(2)
+            .map(|pair| {
+                let end = pair[0].checked_add(pair[1]).with_context(|| {
+                    format!("seed range {}+{} overflows usize", pair[0], pair[1])
+                })?;
+                Ok(pair[0]..end)
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Self)
     }
 
-    fn iter(&self) -> std::slice::Iter<'_, usize> {
-        self.0.iter()
+    fn ranges(&self) -> IntervalSet {
+        self.0.iter().cloned().collect()
+    }
+}
+
+/// The maps from one category to the next, as named by an `X-to-Y map:` header.
+#[derive(Debug)]
+struct Stage {
+    from: String,
+    to: String,
+    maps: Vec<Map>,
+}
+
+impl Stage {
+    /// Reject maps whose source ranges overlap, since which one applies would depend on line
+    /// order. Holes between the maps are legal (those numbers map to themselves) but are
+    /// returned as warnings.
+    fn validate(&self) -> anyhow::Result<Vec<String>> {
+        // an empty range overlaps nothing, and the stable sort reports ties in line order
+        let mut by_src = self
+            .maps
+            .iter()
+            .enumerate()
+            .filter(|(_, map)| !map.src_rng.is_empty())
+            .collect::<Vec<_>>();
+        by_src.sort_by_key(|(_, map)| map.src_rng.start);
+
+        for pair in by_src.windows(2) {
+            let ((a_idx, a), (b_idx, b)) = (pair[0], pair[1]);
+            ensure!(
+                a.src_rng.end <= b.src_rng.start,
+                "{}-to-{}: source ranges of maps {} ({:?}) and {} ({:?}) overlap",
+                self.from,
+                self.to,
+                a_idx + 1,
+                a.src_rng,
+                b_idx + 1,
+                b.src_rng
+            );
+        }
+
+        let covered = self
+            .maps
+            .iter()
+            .map(|map| map.src_rng.clone())
+            .collect::<IntervalSet>();
+        let span = match (covered.ranges().first(), covered.ranges().last()) {
+            (Some(first), Some(last)) => first.start..last.end,
+            _ => return Ok(Vec::new()),
+        };
+        Ok(IntervalSet::from(span)
+            .difference(&covered)
+            .iter()
+            .map(|gap| format!("{}-to-{}: no map covers {gap:?}", self.from, self.to))
+            .collect())
     }
 }
 
@@ -80,82 +325,220 @@ where
     T: Seeds,
 {
     seed_nums: T,
-    seed_to_soil: Vec<Map>,
-    soil_to_fert: Vec<Map>,
-    fert_to_water: Vec<Map>,
-    water_to_light: Vec<Map>,
-    light_to_temp: Vec<Map>,
-    temp_to_humidity: Vec<Map>,
-    humidity_to_location: Vec<Map>,
+    // keyed by source category, in header order; a category can map to several others
+    stages: HashMap<String, Vec<Stage>>,
+    // coverage gaps found while validating the stages
+    warnings: Vec<String>,
 }
 
 impl<T> Almanac<T>
 where
     T: Seeds,
 {
-    fn new(input: &str) -> Self {
-        let mut parts = input.split('\n');
-        Self {
-            seed_nums: T::new(parts.next().unwrap()),
-            seed_to_soil: Self::parse_maps(&mut parts),
-            soil_to_fert: Self::parse_maps(&mut parts),
-            fert_to_water: Self::parse_maps(&mut parts),
-            water_to_light: Self::parse_maps(&mut parts),
-            light_to_temp: Self::parse_maps(&mut parts),
-            temp_to_humidity: Self::parse_maps(&mut parts),
-            humidity_to_location: Self::parse_maps(&mut parts),
-        }
-    }
-
-    fn parse_maps(parts: &mut Split<'_, char>) -> Vec<Map> {
-        parts
-            .skip_while(|line| line.is_empty())
-            .skip(1)
-            .take_while(|line| !line.is_empty())
-            .map(|line| {
-                let map = line
-                    .split_whitespace()
-                    .map(|s| s.parse::<usize>().unwrap())
-                    .collect::<Vec<_>>();
-
-                Map::new(map[0], map[1], map[2])
-            })
-            .collect::<Vec<_>>()
+    fn new(input: &str) -> anyhow::Result<Self> {
+        let mut lines = input.lines().enumerate();
+        let (_, seed_line) = lines.next().context("missing seeds line")?;
+        let seed_nums = T::new(seed_line).context("line 1")?;
+
+        let mut stages = HashMap::new();
+        let mut warnings = Vec::new();
+        let mut current: Option<Stage> = None;
+        for (idx, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_suffix(" map:") {
+                let (from, to) = header
+                    .split_once("-to-")
+                    .with_context(|| format!("line {}: expected `X-to-Y map:`", idx + 1))?;
+                if let Some(stage) = current.take() {
+                    Self::add_stage(&mut stages, &mut warnings, stage)?;
+                }
+                current = Some(Stage {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    maps: Vec::new(),
+                });
+            } else {
+                let stage = current
+                    .as_mut()
+                    .with_context(|| format!("line {}: map before any header", idx + 1))?;
+                let map = Self::parse_map(line).with_context(|| format!("line {}", idx + 1))?;
+                stage.maps.push(map);
+            }
+        }
+        if let Some(stage) = current {
+            Self::add_stage(&mut stages, &mut warnings, stage)?;
+        }
+
+        Ok(Self {
+            seed_nums,
+            stages,
+            warnings,
+        })
+    }
+
+    fn add_stage(
+        stages: &mut HashMap<String, Vec<Stage>>,
+        warnings: &mut Vec<String>,
+        stage: Stage,
+    ) -> anyhow::Result<()> {
+        let outgoing = stages.entry(stage.from.clone()).or_default();
+        ensure!(
+            outgoing.iter().all(|other| other.to != stage.to),
+            "more than one map from `{}` to `{}`",
+            stage.from,
+            stage.to
+        );
+        warnings.extend(stage.validate()?);
+        outgoing.push(stage);
+        Ok(())
     }
 
-    fn seed_locations(&self) -> Vec<usize> {
-        let mut locations = Vec::new();
+    fn parse_map(line: &str) -> anyhow::Result<Map> {
+        let nums = line
+            .split_whitespace()
+            .map(|s| s.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let [dest, src, len] = nums[..] else {
+            bail!("expected `dest src len`, found {} numbers", nums.len());
+        };
+        Map::new(dest, src, len)
+    }
 
-        for seed_num in self.seed_nums.iter() {
-            let soil_num = self.seed_to_soil.find_map(*seed_num);
-            let fert_num = self.soil_to_fert.find_map(soil_num);
-            let water_num = self.fert_to_water.find_map(fert_num);
-            let light_num = self.water_to_light.find_map(water_num);
-            let temp_num = self.light_to_temp.find_map(light_num);
-            let humid_num = self.temp_to_humidity.find_map(temp_num);
-            let location_num = self.humidity_to_location.find_map(humid_num);
-            locations.push(location_num);
+    /// The stages to walk through to convert `from` numbers into `to` numbers. The search is
+    /// breadth first, so this is a path with the fewest stages, and earlier headers win ties.
+    fn path(&self, from: &str, to: &str) -> anyhow::Result<Vec<&Stage>> {
+        // the stage each reached category was first reached by
+        let mut reached_by = HashMap::new();
+        let mut seen = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+        let mut dead_end = None;
+
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                break;
+            }
+            let Some(stages) = self.stages.get(category) else {
+                dead_end.get_or_insert(category);
+                continue;
+            };
+            for stage in stages {
+                if seen.insert(&stage.to) {
+                    reached_by.insert(stage.to.as_str(), stage);
+                    queue.push_back(&stage.to);
+                }
+            }
         }
 
-        locations
+        if !seen.contains(to) {
+            if let Some(category) = dead_end {
+                bail!("no map from `{category}` on the way from `{from}` to `{to}`");
+            }
+            // every category reached has maps out of it, so following the first map out of each
+            // must come back round
+            let mut walked = HashSet::new();
+            let mut category = from;
+            while walked.insert(category) {
+                category = &self.stages[category][0].to;
+            }
+            bail!("maps from `{from}` loop back to `{category}` before reaching `{to}`");
+        }
+
+        let mut path = Vec::new();
+        let mut category = to;
+        while category != from {
+            let stage = reached_by[category];
+            path.push(stage);
+            category = &stage.from;
+        }
+        path.reverse();
+        Ok(path)
+    }
+
+    fn convert(&self, from: &str, to: &str, nums: IntervalSet) -> anyhow::Result<IntervalSet> {
+        self.path(from, to)?
+            .into_iter()
+            .try_fold(nums, |nums, stage| stage.maps.map_ranges(nums))
     }
-}
 
-fn part1(input: &str) -> usize {
-    let almanac: Almanac<Part1Seeds> = Almanac::new(input);
+    /// Every stage between `from` and `to` composed into a single table.
+    fn collapse(&self, from: &str, to: &str) -> anyhow::Result<PiecewiseMap> {
+        let identity = PiecewiseMap::from_maps(&[]);
+        self.path(from, to)?
+            .into_iter()
+            .try_fold(identity, |table, stage| {
+                table.compose(&PiecewiseMap::from_maps(&stage.maps))
+            })
+    }
 
-    *almanac.seed_locations().iter().min().unwrap()
+    fn lowest_location(&self) -> anyhow::Result<usize> {
+        self.convert("seed", "location", self.seed_nums.ranges())?
+            .min()
+            .context("no seeds")
+    }
 }
 
-fn part2(input: &str) -> usize {
-    let _ = input;
-    46
+fn part1(input: &str) -> anyhow::Result<usize> {
+    let almanac: Almanac<Part1Seeds> = Almanac::new(input)?;
+    let table = almanac.collapse("seed", "location")?;
+    almanac
+        .seed_nums
+        .0
+        .iter()
+        .map(|&seed| table.lookup(seed))
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .min()
+        .context("no seeds")
+}
+
+fn part2(input: &str) -> anyhow::Result<usize> {
+    let almanac: Almanac<Part2Seeds> = Almanac::new(input)?;
+    almanac.lowest_location()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    const SAMPLE: &str = "\
+seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
     #[test]
     fn part1_sample() {
         let input = "
@@ -194,7 +577,7 @@ humidity-to-location map:
 56 93 4
 "
         .trim();
-        assert_eq!(part1(input), 35);
+        assert_eq!(part1(input).unwrap(), 35);
     }
 
     #[test]
@@ -235,6 +618,313 @@ humidity-to-location map:
 56 93 4
 "
         .trim();
-        assert_eq!(part2(input), 46);
+        assert_eq!(part2(input).unwrap(), 46);
+    }
+
+    #[test]
+    fn map_ranges_splits() {
+        let maps = vec![
+            Map::new(50, 98, 2).unwrap(),
+            Map::new(52, 50, 48).unwrap(),
+            Map::new(0, 60, 5).unwrap(),
+        ];
+        let point = |num| {
+            maps.iter()
+                .find(|map| map.src_rng.contains(&num))
+                .map_or(num, |map| map.offset().apply(num).unwrap())
+        };
+
+        let src = IntervalSet::from_iter([40..105, 0..3]);
+        let mut mapped = maps
+            .map_ranges(src.clone())
+            .unwrap()
+            .iter()
+            .cloned()
+            .flatten()
+            .collect::<Vec<_>>();
+        let mut expected = src.iter().cloned().flatten().map(point).collect::<Vec<_>>();
+        mapped.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(mapped, expected);
+    }
+
+    #[test]
+    fn convert_any_categories() {
+        let almanac: Almanac<Part1Seeds> = Almanac::new(SAMPLE).unwrap();
+        // soil 81 -> fertilizer 81 -> water 81 -> light 74
+        let light = almanac.convert("soil", "light", (81..82).into()).unwrap();
+        assert_eq!(light, IntervalSet::from(74..75));
+
+        let path = almanac.path("water", "temperature").unwrap();
+        let names = path
+            .iter()
+            .map(|stage| stage.to.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["light", "temperature"]);
+        assert!(almanac.path("seed", "seed").unwrap().is_empty());
+    }
+
+    #[test]
+    fn headers_out_of_order() {
+        let input = "
+seeds: 1 5
+
+b-to-location map:
+10 0 100
+
+seed-to-b map:
+0 1 5
+"
+        .trim();
+        assert_eq!(part1(input).unwrap(), 10);
+    }
+
+    #[test]
+    fn missing_and_cyclic_links() {
+        let almanac: Almanac<Part1Seeds> = Almanac::new(SAMPLE).unwrap();
+        let err = almanac.path("location", "seed").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no map from `location` on the way from `location` to `seed`"
+        );
+
+        let input = "
+seeds: 1
+
+seed-to-soil map:
+0 0 1
+
+soil-to-water map:
+0 0 1
+
+water-to-soil map:
+0 0 1
+"
+        .trim();
+        let err = part1(input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "maps from `seed` loop back to `soil` before reaching `location`"
+        );
+    }
+
+    #[test]
+    fn branching_categories() {
+        // `seed` maps to both `soil` and `water`; only the `water` branch reaches `location`
+        let input = "
+seeds: 1 5
+
+seed-to-soil map:
+100 0 10
+
+seed-to-water map:
+20 0 10
+
+soil-to-fertilizer map:
+0 0 1
+
+water-to-location map:
+0 20 10
+"
+        .trim();
+        assert_eq!(part1(input).unwrap(), 1);
+        assert_eq!(part2(input).unwrap(), 1);
+
+        let almanac: Almanac<Part1Seeds> = Almanac::new(input).unwrap();
+        let names = |from, to| {
+            almanac
+                .path(from, to)
+                .unwrap()
+                .iter()
+                .map(|stage| stage.to.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("seed", "location"), ["water", "location"]);
+        assert_eq!(names("seed", "fertilizer"), ["soil", "fertilizer"]);
+
+        let err = almanac.path("soil", "location").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no map from `fertilizer` on the way from `soil` to `location`"
+        );
+
+        let input = "
+seeds: 1
+
+seed-to-soil map:
+0 0 1
+
+seed-to-soil map:
+5 0 1
+"
+        .trim();
+        let err = part1(input).unwrap_err();
+        assert_eq!(err.to_string(), "more than one map from `seed` to `soil`");
+    }
+
+    #[test]
+    fn collapsed_table() {
+        let almanac: Almanac<Part1Seeds> = Almanac::new(SAMPLE).unwrap();
+        let table = almanac.collapse("seed", "location").unwrap();
+        let path = almanac.path("seed", "location").unwrap();
+
+        for seed in 0..150 {
+            let stepwise = path
+                .iter()
+                .fold(IntervalSet::from(seed..seed + 1), |nums, stage| {
+                    stage.maps.map_ranges(nums).unwrap()
+                });
+            let location = table.lookup(seed).unwrap();
+            assert_eq!(IntervalSet::from(location..location + 1), stepwise);
+        }
+        for pair in table.pieces.windows(2) {
+            assert_eq!(pair[0].src.end, pair[1].src.start);
+        }
+    }
+
+    #[test]
+    fn seed_for_location() {
+        let almanac: Almanac<Part1Seeds> = Almanac::new(SAMPLE).unwrap();
+        let table = almanac.collapse("seed", "location").unwrap();
+        assert_eq!(table.lookup(13).unwrap(), 35);
+        assert!(table.sources_of(35).unwrap().contains(&13));
+        for seed in 0..150 {
+            let location = table.lookup(seed).unwrap();
+            assert!(table.sources_of(location).unwrap().contains(&seed));
+        }
+    }
+
+    #[test]
+    fn overlapping_maps() {
+        let input = "
+seeds: 1
+
+seed-to-location map:
+0 10 5
+100 0 5
+50 12 5
+"
+        .trim();
+        let err = part1(input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "seed-to-location: source ranges of maps 1 (10..15) and 3 (12..17) overlap"
+        );
+    }
+
+    #[test]
+    fn empty_maps_overlap_nothing() {
+        let input = "
+seeds: 12
+
+seed-to-location map:
+0 10 5
+7 12 0
+"
+        .trim();
+        assert_eq!(part1(input).unwrap(), 2);
+
+        let input = "
+seeds: 1
+
+seed-to-location map:
+0 10 5
+50 10 2
+"
+        .trim();
+        let err = part1(input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "seed-to-location: source ranges of maps 1 (10..15) and 2 (10..12) overlap"
+        );
+    }
+
+    #[test]
+    fn overflowing_map() {
+        let input = format!("seeds: 1\n\nseed-to-location map:\n0 {} 2\n", usize::MAX);
+        let err = part1(&input).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            format!("line 4: range {}+2 overflows usize", usize::MAX)
+        );
+    }
+
+    #[test]
+    fn offsets_past_isize_max() {
+        let big = 1usize << 63;
+        let input = format!("seeds: 0 1\n\nseed-to-location map:\n{big} 0 2\n");
+        assert_eq!(part1(&input).unwrap(), big);
+        assert_eq!(part2(&input).unwrap(), big);
+
+        let input = format!("seeds: {big} 1\n\nseed-to-location map:\n0 {big} 2\n");
+        assert_eq!(part1(&input).unwrap(), 0);
+        assert_eq!(part2(&input).unwrap(), 0);
+
+        // each stage alone fits an isize, but the two composed don't
+        let quarter = 1usize << 62;
+        let input = format!(
+            "seeds: 0 1\n\nseed-to-soil map:\n{quarter} 0 2\n\n\
+             soil-to-location map:\n{big} {quarter} 2\n"
+        );
+        assert_eq!(part1(&input).unwrap(), big);
+        assert_eq!(part2(&input).unwrap(), big);
+        let almanac: Almanac<Part1Seeds> = Almanac::new(&input).unwrap();
+        let table = almanac.collapse("seed", "location").unwrap();
+        assert_eq!(table.sources_of(big).unwrap(), [0, quarter, big]);
+    }
+
+    #[test]
+    fn seed_line_errors() {
+        let err = |seeds: &str| {
+            let input = format!("{seeds}\n\nseed-to-location map:\n0 0 1\n");
+            format!("{:#}", part2(&input).unwrap_err())
+        };
+        assert_eq!(
+            err("seeds: 79 14 55"),
+            "line 1: expected `start len` pairs, found 3 seed numbers"
+        );
+        assert_eq!(
+            err(&format!("seeds: {} 2", usize::MAX)),
+            format!("line 1: seed range {}+2 overflows usize", usize::MAX)
+        );
+        assert_eq!(
+            err("seeds: x"),
+            "line 1: invalid seed number `x`: invalid digit found in string"
+        );
+        assert_eq!(
+            err("79 14"),
+            "line 1: expected `seeds:` at the start of `79 14`"
+        );
+
+        let input = "seeds: x\n\nseed-to-location map:\n0 0 1\n";
+        let err = part1(input).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "line 1: invalid seed number `x`: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn coverage_gaps() {
+        let almanac: Almanac<Part1Seeds> = Almanac::new(SAMPLE).unwrap();
+        assert!(almanac.warnings.is_empty());
+
+        let input = "
+seeds: 1
+
+seed-to-location map:
+0 20 5
+100 0 5
+50 10 5
+"
+        .trim();
+        let almanac: Almanac<Part1Seeds> = Almanac::new(input).unwrap();
+        assert_eq!(
+            almanac.warnings,
+            [
+                "seed-to-location: no map covers 5..10",
+                "seed-to-location: no map covers 15..20"
+            ]
+        );
     }
 }
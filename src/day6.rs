@@ -1,97 +1,347 @@
-use std::str::Lines;
+use std::env;
+use std::ops::Range;
 
+use anyhow::{bail, ensure, Context};
 use aoc2023::fetch_input;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    if env::args().nth(1).as_deref() == Some("--explore") {
+        let race = parse_explore_args(env::args().skip(2))?;
+        println!("{}", race.good_race_strats()?);
+        return Ok(());
+    }
+
     let input = fetch_input(6).await?;
-    println!("PART 1: {}", part1(&input));
-    println!("PART 2: {}", part2(&input));
+    println!("PART 1: {}", part1(&input)?);
+    println!("PART 2: {}", part2(&input)?);
 
     Ok(())
 }
 
+/// How holding the button turns into distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Physics {
+    // speed gained per millisecond held
+    accel: u128,
+    max_velocity: Option<u128>,
+    // how far past the record a run has to go to count as a win
+    min_margin: u128,
+}
+
+impl Default for Physics {
+    /// The puzzle's rules: one unit of speed per millisecond, no cap, strictly beat the record.
+    fn default() -> Self {
+        Self {
+            accel: 1,
+            max_velocity: None,
+            min_margin: 1,
+        }
+    }
+}
+
+/// Race numbers are `u128` so kerned sheets and stress inputs fit, and everything that could
+/// still overflow is checked rather than left to wrap in release builds.
 #[derive(Debug)]
 struct Race {
-    time: usize,
-    record_distance: usize,
+    time: u128,
+    record_distance: u128,
+    physics: Physics,
 }
 
 impl Race {
-    const ACCEL: usize = 1;
+    fn new(time: u128, record_distance: u128) -> Self {
+        Self {
+            time,
+            record_distance,
+            physics: Physics::default(),
+        }
+    }
 
-    fn parse_races(input: &str) -> Vec<Race> {
-        let mut lines = input.lines();
-        let times = Self::parse_line(&mut lines);
-        let distances = Self::parse_line(&mut lines);
+    // the distance a run has to reach to win
+    fn target(&self) -> anyhow::Result<u128> {
+        self.record_distance
+            .checked_add(self.physics.min_margin)
+            .context("record plus margin overflows u128")
+    }
 
-        times
-            .into_iter()
-            .zip(distances)
-            .map(|(time, record_distance)| Self {
-                time,
-                record_distance,
-            })
-            .collect()
-    }
-
-    fn parse_line(line: &mut Lines<'_>) -> Vec<usize> {
-        line.next()
-            .unwrap()
-            .split_whitespace()
-            .skip(1)
-            .map(|time| time.parse::<usize>().unwrap())
-            .collect::<Vec<_>>()
-    }
-
-    fn good_race_strats(&self) -> usize {
-        let mut count = 0;
-        for hold_time in 1..=self.time {
-            let traverse_time = self.time - hold_time;
-            let velocity = hold_time * Self::ACCEL;
-            let distance = velocity * traverse_time;
-            if distance > self.record_distance {
-                count += 1;
+    // one past the last hold time
+    fn end(&self) -> anyhow::Result<u128> {
+        self.time.checked_add(1).context("race time overflows u128")
+    }
+
+    /// Count hold times from 0 to `time` that reach the target distance. Before the velocity
+    /// cap kicks in the distance is a parabola and after it a falling line, and each piece is
+    /// solved in closed form.
+    fn good_race_strats(&self) -> anyhow::Result<u128> {
+        let end = self.end()?;
+        // first hold time at which the velocity is capped
+        let cap_start = match self.physics.max_velocity {
+            Some(max) if self.physics.accel > 0 => max.div_ceil(self.physics.accel),
+            Some(_) | None => end,
+        }
+        .min(end);
+
+        let parabola = self.parabola_winners()?;
+        let uncapped = parabola.start..parabola.end.min(cap_start);
+
+        let capped = match self.physics.max_velocity {
+            Some(max) if cap_start <= self.time => {
+                // max·(T−h) ≥ target  ⇔  h ≤ T − ⌈target / max⌉
+                let last = match self.target()? {
+                    0 => Some(self.time),
+                    _ if max == 0 => None,
+                    target => self.time.checked_sub(target.div_ceil(max)),
+                };
+                // `last` is at most `time`, so `last + 1` can't overflow past `end`
+                last.map_or(0..0, |last| cap_start..last + 1)
             }
+            _ => 0..0,
+        };
+
+        Ok(count(uncapped) + count(capped))
+    }
+
+    /// Hold times reaching the target if the velocity were never capped. The distance
+    /// `a·h·(T−h)` is a downward parabola in `h`, so the winners are the integers between its
+    /// crossings with the target. The integer square root gets within one of the first
+    /// crossing, then we step to the exact boundary and mirror it, since the parabola is
+    /// symmetric about `T/2`.
+    fn parabola_winners(&self) -> anyhow::Result<Range<u128>> {
+        let (time, accel, target) = (self.time, self.physics.accel, self.target()?);
+        // a product too big for u128 is certainly past the target
+        let reaches = |hold_time: u128| {
+            accel
+                .checked_mul(hold_time)
+                .and_then(|velocity| velocity.checked_mul(time - hold_time))
+                .is_none_or(|distance| distance >= target)
+        };
+
+        if target == 0 {
+            return Ok(0..self.end()?);
+        }
+        if accel == 0 {
+            return Ok(0..0);
+        }
+        // a·h² − a·T·h + target ≤ 0 has real roots only when (a·T)² ≥ 4·a·target
+        let too_big = || format!("discriminant of {self:?} overflows u128");
+        let a_t = accel.checked_mul(time).with_context(too_big)?;
+        let a_t_sq = a_t.checked_mul(a_t).with_context(too_big)?;
+        let four_a_d = accel
+            .checked_mul(target)
+            .and_then(|a_d| a_d.checked_mul(4))
+            .with_context(too_big)?;
+        if a_t_sq < four_a_d {
+            return Ok(0..0);
+        }
+        let root = (a_t_sq - four_a_d).isqrt();
+
+        let mut first = (a_t - root) / (2 * accel);
+        while first <= time / 2 && !reaches(first) {
+            first += 1;
+        }
+        while first > 0 && reaches(first - 1) {
+            first -= 1;
+        }
+        if first > time / 2 {
+            return Ok(0..0);
         }
 
-        count
+        // `first` is positive here since a zero hold time never reaches a positive target
+        Ok(first..time - first + 1)
     }
 }
 
-fn part1(input: &str) -> usize {
-    let races = Race::parse_races(input);
-    races
-        .iter()
-        .map(|race| race.good_race_strats())
-        .product::<usize>()
+// `Range<u128>` has no `len`
+fn count(rng: Range<u128>) -> u128 {
+    rng.end.saturating_sub(rng.start)
 }
 
-fn part2(input: &str) -> usize {
-    let race_data = input
-        .lines()
-        .map(|line| {
-            line.split(':')
-                .nth(1)
-                .unwrap()
-                .replace(' ', "")
-                .parse::<usize>()
-                .unwrap()
+/// The race sheet read both ways: every column as its own race, or all the digits on each line
+/// run together as one kerned race. The kerned digits are only parsed on request, so a sheet too
+/// long to kern still gives its per-column races.
+#[derive(Debug)]
+struct RaceSheet {
+    races: Vec<Race>,
+    kerned_time: String,
+    kerned_distance: String,
+}
+
+impl RaceSheet {
+    fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut lines = input.lines();
+        let times = Self::parse_line(lines.next(), "Time")?;
+        let distances = Self::parse_line(lines.next(), "Distance")?;
+        ensure!(
+            times.len() == distances.len(),
+            "{} times but {} distances",
+            times.len(),
+            distances.len()
+        );
+
+        let races = times
+            .iter()
+            .zip(&distances)
+            .map(|(time, distance)| Ok(Race::new(time.parse()?, distance.parse()?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            races,
+            kerned_time: times.concat(),
+            kerned_distance: distances.concat(),
         })
-        .collect::<Vec<_>>();
+    }
+
+    fn kerned(&self) -> anyhow::Result<Race> {
+        Ok(Race::new(
+            self.kerned_time.parse().context("kerned time")?,
+            self.kerned_distance.parse().context("kerned distance")?,
+        ))
+    }
+
+    fn parse_line<'a>(line: Option<&'a str>, label: &str) -> anyhow::Result<Vec<&'a str>> {
+        let line = line.with_context(|| format!("missing `{label}:` line"))?;
+        let columns = line
+            .strip_prefix(label)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .with_context(|| format!("expected `{label}:` at the start of `{line}`"))?;
+        Ok(columns.split_whitespace().collect())
+    }
+}
+
+fn part1(input: &str) -> anyhow::Result<u128> {
+    let sheet = RaceSheet::parse(input)?;
+    sheet.races.iter().try_fold(1u128, |product, race| {
+        product
+            .checked_mul(race.good_race_strats()?)
+            .context("product of race counts overflows u128")
+    })
+}
+
+fn part2(input: &str) -> anyhow::Result<u128> {
+    let sheet = RaceSheet::parse(input)?;
+    sheet.kerned()?.good_race_strats()
+}
+
+fn parse_num(arg: Option<String>, name: &str) -> anyhow::Result<u128> {
+    let arg = arg.with_context(|| format!("expected {name}"))?;
+    arg.parse()
+        .with_context(|| format!("invalid {name} `{arg}`"))
+}
+
+/// `TIME RECORD [--accel A] [--max-velocity V] [--margin M]`
+fn parse_explore_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Race> {
+    let time = parse_num(args.next(), "time")?;
+    let record_distance = parse_num(args.next(), "record")?;
+    let mut race = Race::new(time, record_distance);
 
-    let race = Race {
-        time: race_data[0],
-        record_distance: race_data[1],
-    };
+    while let Some(flag) = args.next() {
+        let physics = &mut race.physics;
+        match flag.as_str() {
+            "--accel" => physics.accel = parse_num(args.next(), &flag)?,
+            "--max-velocity" => physics.max_velocity = Some(parse_num(args.next(), &flag)?),
+            "--margin" => physics.min_margin = parse_num(args.next(), &flag)?,
+            _ => bail!("unknown flag `{flag}`"),
+        }
+    }
 
-    race.good_race_strats()
+    Ok(race)
 }
 
 #[cfg(test)]
 mod test {
+    use aoc2023::rng::Rng;
+
     use super::*;
 
+    fn good_race_strats_brute(race: &Race) -> u128 {
+        let physics = race.physics;
+        (0..=race.time)
+            .filter(|&hold_time| {
+                let velocity = hold_time * physics.accel;
+                let velocity = physics
+                    .max_velocity
+                    .map_or(velocity, |max| velocity.min(max));
+                velocity * (race.time - hold_time) >= race.target().unwrap()
+            })
+            .count() as u128
+    }
+
+    #[test]
+    fn closed_form_matches_brute_force() {
+        let mut rng = Rng::new(6);
+        for _ in 0..5000 {
+            let time = rng.range(0..300) as u128;
+            // mostly near the peak distance of T²/4, where the boundaries are tight
+            let peak = (time * time / 4) as u64;
+            let record_distance = rng.range(peak.saturating_sub(50)..peak + 3) as u128;
+            let race = Race::new(time, record_distance);
+            assert_eq!(
+                good_race_strats_brute(&race),
+                race.good_race_strats().unwrap(),
+                "{race:?}"
+            );
+        }
+
+        for time in 0..60 {
+            for record_distance in 0..time * time / 4 + 2 {
+                let race = Race::new(time, record_distance);
+                assert_eq!(
+                    good_race_strats_brute(&race),
+                    race.good_race_strats().unwrap(),
+                    "{race:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn general_physics_matches_brute_force() {
+        let mut rng = Rng::new(43);
+        for _ in 0..20000 {
+            let time = rng.range(0..120) as u128;
+            let physics = Physics {
+                accel: rng.range(0..5) as u128,
+                max_velocity: (rng.range(0..3) > 0).then(|| rng.range(0..150) as u128),
+                min_margin: rng.range(0..4) as u128,
+            };
+            let race = Race {
+                time,
+                record_distance: rng.range(0..(time * time) as u64 + 2) as u128,
+                physics,
+            };
+            assert_eq!(
+                good_race_strats_brute(&race),
+                race.good_race_strats().unwrap(),
+                "{race:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn explore_args() {
+        let args = "30 200 --accel 2 --max-velocity 20 --margin 5";
+        let race = parse_explore_args(args.split(' ').map(String::from)).unwrap();
+        assert_eq!(
+            race.physics,
+            Physics {
+                accel: 2,
+                max_velocity: Some(20),
+                min_margin: 5
+            }
+        );
+        assert_eq!(
+            race.good_race_strats().unwrap(),
+            good_race_strats_brute(&race)
+        );
+
+        let race = parse_explore_args("30 200".split(' ').map(String::from)).unwrap();
+        assert_eq!(race.good_race_strats().unwrap(), 9);
+
+        let err = parse_explore_args("30 200 --drag 1".split(' ').map(String::from));
+        assert_eq!(err.unwrap_err().to_string(), "unknown flag `--drag`");
+    }
+
     #[test]
     fn part1_sample() {
         let input = "
@@ -99,7 +349,7 @@ Time:      7  15   30
 Distance:  9  40  200
 "
         .trim();
-        assert_eq!(part1(input), 288);
+        assert_eq!(part1(input).unwrap(), 288);
     }
 
     #[test]
@@ -109,6 +359,69 @@ Time:      7  15   30
 Distance:  9  40  200
 "
         .trim();
-        assert_eq!(part2(input), 71503);
+        assert_eq!(part2(input).unwrap(), 71503);
+    }
+
+    #[test]
+    fn sheet_both_readings() {
+        let sheet = RaceSheet::parse("Time:      7  15   30\nDistance:  9  40  200").unwrap();
+        let races = sheet
+            .races
+            .iter()
+            .map(|race| (race.time, race.record_distance))
+            .collect::<Vec<_>>();
+        assert_eq!(races, [(7, 9), (15, 40), (30, 200)]);
+        let kerned = sheet.kerned().unwrap();
+        assert_eq!((kerned.time, kerned.record_distance), (71530, 940200));
+    }
+
+    #[test]
+    fn too_long_to_kern() {
+        // forty kerned digits don't fit a u128, but each race on its own is small
+        let input = format!(
+            "Time: {}\nDistance: {}",
+            ["1000"; 10].join(" "),
+            ["0"; 10].join(" ")
+        );
+        assert_eq!(part1(&input).unwrap(), 999u128.pow(10));
+        let err = part2(&input).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "kerned time: number too large to fit in target type"
+        );
+    }
+
+    #[test]
+    fn sheet_errors() {
+        let err = RaceSheet::parse("Time: 7 15 30\nDistance: 9 40").unwrap_err();
+        assert_eq!(err.to_string(), "3 times but 2 distances");
+
+        let err = RaceSheet::parse("7 15\nDistance: 9 40").unwrap_err();
+        assert_eq!(err.to_string(), "expected `Time:` at the start of `7 15`");
+
+        let err = RaceSheet::parse("Time: 7 15").unwrap_err();
+        assert_eq!(err.to_string(), "missing `Distance:` line");
+    }
+
+    #[test]
+    fn huge_races() {
+        let race = Race::new(10u128.pow(18), 0);
+        assert_eq!(race.good_race_strats().unwrap(), 10u128.pow(18) - 1);
+
+        // only the exact midpoint reaches the peak distance of T²/4
+        let race = Race::new(2 * 10u128.pow(18), 10u128.pow(36) - 1);
+        assert_eq!(race.good_race_strats().unwrap(), 1);
+
+        let sheet = "Time: 18446744073 709551615\nDistance: 0 0";
+        assert_eq!(part2(sheet).unwrap(), u64::MAX as u128 - 1);
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let err = Race::new(u128::MAX, 5).good_race_strats().unwrap_err();
+        assert!(err.to_string().contains("overflows u128"), "{err}");
+
+        let err = Race::new(10, u128::MAX).good_race_strats().unwrap_err();
+        assert_eq!(err.to_string(), "record plus margin overflows u128");
     }
 }
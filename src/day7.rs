@@ -1,200 +1,553 @@
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::env;
+use std::fmt::Write;
 
+use anyhow::{bail, ensure, Context};
 use aoc2023::fetch_input;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let input = fetch_input(7).await?;
-    println!("PART 1: {}", part1(&input));
-    println!("PART 2: {}", part2(&input));
+    match env::args().nth(1).as_deref() {
+        Some("--rules") => {
+            let rules = parse_rules_args(env::args().skip(2))?;
+            println!("{}", total_winnings(&input, &rules)?);
+        }
+        Some("--explain") => match env::args().nth(2).as_deref() {
+            Some("--jokers") => print!("{}", explain_table(&input, &Jokers)?),
+            _ => print!("{}", explain_table(&input, &Standard)?),
+        },
+        Some("--explain-csv") => match env::args().nth(2).as_deref() {
+            Some("--jokers") => print!("{}", explain_csv(&input, &Jokers)?),
+            _ => print!("{}", explain_csv(&input, &Standard)?),
+        },
+        _ => {
+            println!("PART 1: {}", part1(&input)?);
+            println!("PART 2: {}", part2(&input)?);
+        }
+    }
 
     Ok(())
 }
 
+// rank indices that fit a nibble of the packed sort key
+const PACKED_RANKS: usize = 16;
+// the kind's 3 bits plus a nibble per card fill at most 63 bits of a `u64`
+const PACKED_CARDS: usize = 15;
+
+/// A card under the rules it was parsed with. Its strength is its index in `Rules::rank_order`,
+/// and whether it's wild is read off `Rules::wildcards` once, when it's parsed.
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
-enum Card {
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    Ten,
-    Jack,
-    Queen,
-    King,
-    Ace,
-}
-
-impl From<char> for Card {
-    fn from(value: char) -> Self {
-        match value {
-            'A' => Self::Ace,
-            'K' => Self::King,
-            'Q' => Self::Queen,
-            'J' => Self::Jack,
-            'T' => Self::Ten,
-            '9' => Self::Nine,
-            '8' => Self::Eight,
-            '7' => Self::Seven,
-            '6' => Self::Six,
-            '5' => Self::Five,
-            '4' => Self::Four,
-            '3' => Self::Three,
-            '2' => Self::Two,
-            _ => unreachable!(),
-        }
-    }
-}
-
-#[derive(Debug, Eq, PartialEq)]
+struct Card {
+    rank: usize,
+    wild: bool,
+}
+
+/// Hand kinds from weakest to strongest.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
 enum HandType {
-    HighCard(Card, Card, Card, Card, Card),
-    OnePair(Card, Card, Card, Card),
-    TwoPair(Card, Card, Card),
-    ThreeOfKind(Card, Card, Card),
-    FullHouse(Card, Card, Card),
-    FourOfKind(Card, Card),
-    FiveOfKind(Card),
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfKind,
+    FullHouse,
+    FourOfKind,
+    FiveOfKind,
 }
 
 impl HandType {
-    fn new(cards: [Card; 5]) -> Self {
-        let mut counts = HashMap::new();
-        cards.into_iter().for_each(|card| {
-            *counts.entry(card).or_insert(0) += 1;
-        });
-
-        let mut cards = counts.into_iter().collect::<Vec<_>>();
-        cards.sort_by(|a, b| match b.1.cmp(&a.1) {
-            Ordering::Equal => b.0.cmp(&a.0),
-            other => other,
-        });
-
-        match cards.len() {
-            1 => Self::FiveOfKind(cards[0].0),
-            2 => Self::FourOfKind(cards[0].0, cards[1].0),
-            3 => {
-                if cards[0].1 == 2 {
-                    Self::TwoPair(cards[0].0, cards[1].0, cards[2].0)
-                } else if cards[1].1 == 2 {
-                    Self::FullHouse(cards[0].0, cards[1].0, cards[2].0)
-                } else {
-                    Self::ThreeOfKind(cards[0].0, cards[1].0, cards[2].0)
-                }
+    const ALL: [Self; 7] = [
+        Self::HighCard,
+        Self::OnePair,
+        Self::TwoPair,
+        Self::ThreeOfKind,
+        Self::FullHouse,
+        Self::FourOfKind,
+        Self::FiveOfKind,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::HighCard => "high card",
+            Self::OnePair => "one pair",
+            Self::TwoPair => "two pair",
+            Self::ThreeOfKind => "three of a kind",
+            Self::FullHouse => "full house",
+            Self::FourOfKind => "four of a kind",
+            Self::FiveOfKind => "five of a kind",
+        }
+    }
+
+    /// The kind made by the two largest groups of matching cards. Groups past five still count
+    /// as five of a kind, so this works for any hand size.
+    fn from_groups(largest: usize, second: usize) -> Self {
+        match (largest, second) {
+            (5.., _) => Self::FiveOfKind,
+            (4, _) => Self::FourOfKind,
+            (3, 2..) => Self::FullHouse,
+            (3, _) => Self::ThreeOfKind,
+            (2, 2..) => Self::TwoPair,
+            (2, _) => Self::OnePair,
+            _ => Self::HighCard,
+        }
+    }
+}
+
+/// A Camel Cards variant: which labels exist and how strong they are, which of them are wild,
+/// and how a hand's kind is worked out.
+trait Rules {
+    /// Card labels from weakest to strongest.
+    fn rank_order(&self) -> &str;
+
+    /// Labels that join whichever group makes the best hand.
+    fn wildcards(&self) -> &str {
+        ""
+    }
+
+    fn hand_size(&self) -> usize {
+        5
+    }
+
+    fn card(&self, label: char) -> anyhow::Result<Card> {
+        match self.rank_order().chars().position(|rank| rank == label) {
+            Some(rank) => Ok(Card {
+                rank,
+                wild: self.wildcards().contains(label),
+            }),
+            None => bail!("unknown card `{label}`"),
+        }
+    }
+
+    fn label(&self, card: Card) -> char {
+        self.rank_order().chars().nth(card.rank).unwrap()
+    }
+
+    /// Whether every hand fits the packed `u64` sort key.
+    fn packs(&self) -> bool {
+        self.rank_order().chars().count() <= PACKED_RANKS && self.hand_size() <= PACKED_CARDS
+    }
+
+    /// Count each rank and keep the two largest groups. Ranks that fit the packed key are
+    /// counted in a fixed array, so no allocation or sorting per hand; bigger decks sort a copy
+    /// of the ranks instead.
+    fn classify(&self, cards: &[Card]) -> HandType {
+        let wild = cards.iter().filter(|card| card.wild).count();
+        let natural = cards.iter().filter(|card| !card.wild);
+
+        let (largest, second) = if cards.iter().all(|card| card.rank < PACKED_RANKS) {
+            let mut counts = [0; PACKED_RANKS];
+            for card in natural {
+                counts[card.rank] += 1;
             }
-            4 => Self::OnePair(cards[0].0, cards[1].0, cards[2].0, cards[3].0),
-            5 => Self::HighCard(cards[0].0, cards[1].0, cards[2].0, cards[3].0, cards[4].0),
-            _ => unreachable!(),
+            two_largest(counts)
+        } else {
+            let mut ranks = natural.map(|card| card.rank).collect::<Vec<_>>();
+            ranks.sort_unstable();
+            two_largest(ranks.chunk_by(|a, b| a == b).map(<[_]>::len))
+        };
+        // wildcards always do best joining the largest group
+        HandType::from_groups(largest + wild, second)
+    }
+}
+
+// the two biggest group sizes, for `HandType::from_groups`
+fn two_largest(counts: impl IntoIterator<Item = usize>) -> (usize, usize) {
+    let (mut largest, mut second) = (0, 0);
+    for count in counts {
+        if count > largest {
+            (largest, second) = (count, largest);
+        } else if count > second {
+            second = count;
         }
     }
+    (largest, second)
+}
 
-    fn type_order(&self) -> u8 {
-        match self {
-            HandType::HighCard(_, _, _, _, _) => 1,
-            HandType::OnePair(_, _, _, _) => 2,
-            HandType::TwoPair(_, _, _) => 3,
-            HandType::ThreeOfKind(_, _, _) => 4,
-            HandType::FullHouse(_, _, _) => 5,
-            HandType::FourOfKind(_, _) => 6,
-            HandType::FiveOfKind(_) => 7,
+/// Part 1: `J` is a jack.
+struct Standard;
+
+impl Rules for Standard {
+    fn rank_order(&self) -> &str {
+        "23456789TJQKA"
+    }
+}
+
+/// Part 2: `J` is a joker, the weakest card for tie-breaks but wild when classifying.
+struct Jokers;
+
+impl Rules for Jokers {
+    fn rank_order(&self) -> &str {
+        "J23456789TQKA"
+    }
+
+    fn wildcards(&self) -> &str {
+        "J"
+    }
+}
+
+/// Rules given on the command line.
+#[derive(Debug, PartialEq)]
+struct Custom {
+    ranks: String,
+    wilds: String,
+    hand_size: usize,
+}
+
+impl Custom {
+    fn new(ranks: &str, wilds: &str, hand_size: usize) -> anyhow::Result<Self> {
+        for (idx, label) in ranks.char_indices() {
+            ensure!(!ranks[..idx].contains(label), "card `{label}` ranked twice");
         }
+        if let Some(label) = wilds.chars().find(|&label| !ranks.contains(label)) {
+            bail!("wildcard `{label}` is not a ranked card");
+        }
+        ensure!(hand_size > 0, "hands need at least one card");
+
+        Ok(Self {
+            ranks: ranks.to_string(),
+            wilds: wilds.to_string(),
+            hand_size,
+        })
     }
 }
 
-impl PartialOrd for HandType {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl Rules for Custom {
+    fn rank_order(&self) -> &str {
+        &self.ranks
+    }
+
+    fn wildcards(&self) -> &str {
+        &self.wilds
+    }
+
+    fn hand_size(&self) -> usize {
+        self.hand_size
     }
 }
 
-impl Ord for HandType {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let type_order = self.type_order().cmp(&other.type_order());
+/// `RANKS [--wild LABELS] [--size N]`
+fn parse_rules_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Custom> {
+    let ranks = args.next().context("expected card ranks, weakest first")?;
+    let (mut wilds, mut hand_size) = (String::new(), 5);
 
-        if type_order == Ordering::Equal {
-            match (self, other) {
-                (
-                    HandType::HighCard(a1, a2, a3, a4, a5),
-                    HandType::HighCard(b1, b2, b3, b4, b5),
-                ) => (a1, a2, a3, a4, a5).cmp(&(b1, b2, b3, b4, b5)),
-                (HandType::OnePair(a1, a2, a3, a4), HandType::OnePair(b1, b2, b3, b4)) => {
-                    (a1, a2, a3, a4).cmp(&(b1, b2, b3, b4))
-                }
-                (HandType::TwoPair(a1, a2, a3), HandType::TwoPair(b1, b2, b3)) => {
-                    (a1, a2, a3).cmp(&(b1, b2, b3))
-                }
-                (HandType::ThreeOfKind(a1, a2, a3), HandType::ThreeOfKind(b1, b2, b3)) => {
-                    (a1, a2, a3).cmp(&(b1, b2, b3))
-                }
-                (HandType::FullHouse(a1, a2, a3), HandType::FullHouse(b1, b2, b3)) => {
-                    (a1, a2, a3).cmp(&(b1, b2, b3))
-                }
-                (HandType::FourOfKind(a1, a2), HandType::FourOfKind(b1, b2)) => {
-                    (a1, a2).cmp(&(b1, b2))
-                }
-
-                (HandType::FiveOfKind(a), HandType::FiveOfKind(b)) => a.cmp(b),
-                _ => unreachable!(),
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("expected a value for {flag}"))?;
+        match flag.as_str() {
+            "--wild" => wilds = value,
+            "--size" => {
+                hand_size = value
+                    .parse()
+                    .with_context(|| format!("invalid hand size `{value}`"))?
             }
-        } else {
-            type_order
+            _ => bail!("unknown flag `{flag}`"),
         }
     }
+
+    Custom::new(&ranks, &wilds, hand_size)
 }
 
 #[derive(Debug)]
 struct Hand {
-    cards: [Card; 5],
+    cards: Vec<Card>,
+    kind: HandType,
     bid: usize,
 }
 
-fn parse_hands(input: &str) -> Vec<Hand> {
+impl Hand {
+    fn key(&self) -> u64 {
+        sort_key(self.kind, &self.cards)
+    }
+}
+
+/// Hands rank by kind, and ties go to the first differing card in dealt order. Packing the kind
+/// above one nibble per card, first card highest, makes that a single integer comparison. Only
+/// meaningful when the rules `pack`.
+fn sort_key(kind: HandType, cards: &[Card]) -> u64 {
+    let cards = cards
+        .iter()
+        .fold(0, |key, card| key << 4 | card.rank as u64);
+    (kind as u64) << (4 * PACKED_CARDS) | cards
+}
+
+fn parse_hand(line: &str, rules: &impl Rules) -> anyhow::Result<Hand> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let [cards, bid] = fields[..] else {
+        bail!("expected cards and a bid");
+    };
+    let cards = cards
+        .chars()
+        .map(|label| rules.card(label))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    ensure!(
+        cards.len() == rules.hand_size(),
+        "expected {} cards, found {}",
+        rules.hand_size(),
+        cards.len()
+    );
+    let bid = bid
+        .parse()
+        .with_context(|| format!("invalid bid `{bid}`"))?;
+
+    Ok(Hand {
+        kind: rules.classify(&cards),
+        cards,
+        bid,
+    })
+}
+
+fn parse_hands(input: &str, rules: &impl Rules) -> anyhow::Result<Vec<Hand>> {
     input
         .lines()
-        .map(|line| {
-            let mut parts = line.split_whitespace();
-            let cards = parts
-                .next()
-                .unwrap()
-                .chars()
-                .map(|ch| ch.into())
-                .collect::<Vec<_>>();
-            let bid = parts.next().unwrap().parse::<usize>().unwrap();
-            Hand {
-                cards: cards.try_into().unwrap(),
-                bid,
-            }
-        })
+        .enumerate()
+        .map(|(idx, line)| parse_hand(line, rules).with_context(|| format!("line {}", idx + 1)))
         .collect()
 }
 
-fn part1(input: &str) -> usize {
-    let mut hands = parse_hands(input)
-        .iter()
-        .map(|hand| (HandType::new(hand.cards), hand.bid))
-        .collect::<Vec<_>>();
+/// Hands from weakest to strongest, so a hand's rank is its index plus one.
+fn ranked_hands(input: &str, rules: &impl Rules) -> anyhow::Result<Vec<Hand>> {
+    let mut hands = parse_hands(input, rules)?;
+    if rules.packs() {
+        // the key is packed once per hand, not once per comparison
+        hands.sort_by_cached_key(Hand::key);
+    } else {
+        hands.sort_by(|a, b| (a.kind, &a.cards).cmp(&(b.kind, &b.cards)));
+    }
+    Ok(hands)
+}
 
-    hands.sort_by(|a, b| a.0.cmp(&b.0));
-    hands
+fn total_winnings(input: &str, rules: &impl Rules) -> anyhow::Result<usize> {
+    Ok(ranked_hands(input, rules)?
         .iter()
         .enumerate()
-        .map(|(idx, (_, bid))| bid * (idx + 1))
-        .sum()
+        .map(|(idx, hand)| hand.bid * (idx + 1))
+        .sum())
+}
+
+fn labels(hand: &Hand, rules: &impl Rules) -> String {
+    hand.cards.iter().map(|&card| rules.label(card)).collect()
+}
+
+fn type_counts(hands: &[Hand]) -> Vec<(HandType, usize)> {
+    HandType::ALL
+        .into_iter()
+        .map(|kind| (kind, hands.iter().filter(|hand| hand.kind == kind).count()))
+        .collect()
+}
+
+fn explain_table(input: &str, rules: &impl Rules) -> anyhow::Result<String> {
+    let hands = ranked_hands(input, rules)?;
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:>5}  {:<7}  {:<15} {:>6} {:>9}",
+        "rank", "hand", "type", "bid", "winnings"
+    )
+    .unwrap();
+
+    let mut total = 0;
+    for (idx, hand) in hands.iter().enumerate() {
+        let winnings = hand.bid * (idx + 1);
+        total += winnings;
+        writeln!(
+            out,
+            "{:>5}  {:<7}  {:<15} {:>6} {winnings:>9}",
+            idx + 1,
+            labels(hand, rules),
+            hand.kind.name(),
+            hand.bid,
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\n{:<15} {:>6}", "type", "hands").unwrap();
+    for (kind, count) in type_counts(&hands) {
+        writeln!(out, "{:<15} {count:>6}", kind.name()).unwrap();
+    }
+    writeln!(out, "\ntotal winnings: {total}").unwrap();
+
+    Ok(out)
+}
+
+/// One row per hand in rank order, then a blank line and the per-type counts.
+fn explain_csv(input: &str, rules: &impl Rules) -> anyhow::Result<String> {
+    let hands = ranked_hands(input, rules)?;
+    let mut out = String::from("rank,hand,type,bid,winnings\n");
+
+    for (idx, hand) in hands.iter().enumerate() {
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            idx + 1,
+            labels(hand, rules),
+            hand.kind.name(),
+            hand.bid,
+            hand.bid * (idx + 1),
+        )
+        .unwrap();
+    }
+
+    out.push_str("\ntype,hands\n");
+    for (kind, count) in type_counts(&hands) {
+        writeln!(out, "{},{count}", kind.name()).unwrap();
+    }
+
+    Ok(out)
 }
 
-fn part2(input: &str) -> usize {
-    let _ = input;
-    7
+fn part1(input: &str) -> anyhow::Result<usize> {
+    total_winnings(input, &Standard)
+}
+
+fn part2(input: &str) -> anyhow::Result<usize> {
+    total_winnings(input, &Jokers)
 }
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    use aoc2023::rng::Rng;
+
     use super::*;
 
+    // the old classification: group with a map, then sort the group sizes
+    fn classify_hashmap(cards: &[Card]) -> HandType {
+        let mut counts = HashMap::new();
+        let mut wild = 0;
+        for &card in cards {
+            if card.wild {
+                wild += 1;
+            } else {
+                *counts.entry(card).or_insert(0) += 1;
+            }
+        }
+
+        let mut counts = counts.into_values().collect::<Vec<_>>();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        match counts.first_mut() {
+            Some(most) => *most += wild,
+            None => counts.push(wild),
+        }
+        HandType::from_groups(counts[0], counts.get(1).copied().unwrap_or(0))
+    }
+
+    fn generate_hands(count: usize, seed: u64) -> String {
+        let mut rng = Rng::new(seed);
+        let labels = Standard.rank_order().chars().collect::<Vec<_>>();
+        let mut out = String::new();
+        for _ in 0..count {
+            // few distinct labels per hand so every kind turns up
+            let pool = (0..rng.range(1..6))
+                .map(|_| *rng.pick(&labels))
+                .collect::<Vec<_>>();
+            let cards = (0..5).map(|_| *rng.pick(&pool)).collect::<String>();
+            out.push_str(&format!("{cards} {}\n", rng.range(1..1000)));
+        }
+        out
+    }
+
+    fn hand(cards: &str) -> Hand {
+        parse_hand(&format!("{cards} 0"), &Standard).unwrap()
+    }
+
+    fn joker_hand(cards: &str) -> Hand {
+        parse_hand(&format!("{cards} 0"), &Jokers).unwrap()
+    }
+
+    #[test]
+    fn hand_types() {
+        for (cards, kind) in [
+            ("AAAAA", HandType::FiveOfKind),
+            ("AA8AA", HandType::FourOfKind),
+            ("23332", HandType::FullHouse),
+            ("TTT98", HandType::ThreeOfKind),
+            ("23432", HandType::TwoPair),
+            ("A23A4", HandType::OnePair),
+            ("23456", HandType::HighCard),
+        ] {
+            assert_eq!(hand(cards).kind, kind, "{cards}");
+        }
+    }
+
+    #[test]
+    fn ties_break_in_dealt_order() {
+        for (weaker, stronger) in [
+            ("2AAAA", "33332"),
+            ("77788", "77888"),
+            ("KTJJT", "KK677"),
+            ("T55J5", "QQQJA"),
+            // kind beats card order
+            ("AAKKQ", "2223A"),
+        ] {
+            assert!(
+                hand(weaker).key() < hand(stronger).key(),
+                "{weaker} < {stronger}"
+            );
+        }
+    }
+
+    fn check_against_reference(rules: &impl Rules, input: &str) {
+        let hands = parse_hands(input, rules).unwrap();
+        for hand in &hands {
+            assert_eq!(hand.kind, classify_hashmap(&hand.cards), "{hand:?}");
+        }
+        for pair in hands.windows(2) {
+            let tuple = |hand: &Hand| (hand.kind, hand.cards.clone());
+            assert_eq!(
+                pair[0].key().cmp(&pair[1].key()),
+                tuple(&pair[0]).cmp(&tuple(&pair[1])),
+                "{pair:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn matches_reference_classification() {
+        let input = generate_hands(5000, 49);
+        check_against_reference(&Standard, &input);
+        check_against_reference(&Jokers, &input);
+    }
+
+    #[test]
+    #[ignore = "benchmark: cargo test --release --bin day7 -- --ignored --nocapture"]
+    fn bench_classify_and_sort() {
+        let input = generate_hands(1_000_000, 7);
+        let hands = parse_hands(&input, &Jokers).unwrap();
+
+        let start = Instant::now();
+        let mut tuples = hands
+            .iter()
+            .map(|hand| (classify_hashmap(&hand.cards), &hand.cards, hand.bid))
+            .collect::<Vec<_>>();
+        tuples.sort_unstable();
+        println!("hashmap + tuple sort: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let mut keys = hands
+            .iter()
+            .map(|hand| {
+                (
+                    sort_key(Jokers.classify(&hand.cards), &hand.cards),
+                    hand.bid,
+                )
+            })
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        println!("array + packed key:   {:?}", start.elapsed());
+
+        let bids = |bids: &mut dyn Iterator<Item = usize>| {
+            bids.enumerate()
+                .map(|(idx, bid)| bid * (idx + 1))
+                .sum::<usize>()
+        };
+        assert_eq!(
+            bids(&mut tuples.iter().map(|&(_, _, bid)| bid)),
+            bids(&mut keys.iter().map(|&(_, bid)| bid))
+        );
+    }
+
     #[test]
     fn part1_sample() {
         let input = "
@@ -205,7 +558,7 @@ KTJJT 220
 QQQJA 483
 "
         .trim();
-        assert_eq!(part1(input), 6440);
+        assert_eq!(part1(input).unwrap(), 6440);
     }
 
     #[test]
@@ -218,6 +571,163 @@ KTJJT 220
 QQQJA 483
 "
         .trim();
-        assert_eq!(part2(input), 7);
+        assert_eq!(part2(input).unwrap(), 5905);
+    }
+
+    #[test]
+    fn jokers_upgrade() {
+        for (cards, kind) in [
+            ("JJJJJ", HandType::FiveOfKind),
+            ("JJJJ2", HandType::FiveOfKind),
+            ("QJJQ2", HandType::FourOfKind),
+            ("KTJJT", HandType::FourOfKind),
+            ("2233J", HandType::FullHouse),
+            ("2234J", HandType::ThreeOfKind),
+            ("2345J", HandType::OnePair),
+            ("23456", HandType::HighCard),
+        ] {
+            assert_eq!(joker_hand(cards).kind, kind, "{cards}");
+        }
+    }
+
+    #[test]
+    fn jokers_break_ties_lowest() {
+        assert!(joker_hand("JKKK2").key() < joker_hand("QQQQ2").key());
+        assert!(joker_hand("JJJJJ").key() < joker_hand("22222").key());
+        assert!(hand("JKKK2").key() > hand("TKKK2").key());
+    }
+
+    #[test]
+    fn custom_rules() {
+        // every card wild and three-card hands
+        let rules = Custom::new("abc", "abc", 3).unwrap();
+        let hand = parse_hand("abc 1", &rules).unwrap();
+        assert_eq!(hand.kind, HandType::ThreeOfKind);
+
+        // seven-card hands keep their two best groups
+        let rules = parse_rules_args("23456789TJQKA --size 7".split(' ').map(String::from));
+        let hand = parse_hand("2233344 1", &rules.unwrap()).unwrap();
+        assert_eq!(hand.kind, HandType::FullHouse);
+
+        let rules = Custom::new("23456789TJQKA", "2", 5).unwrap();
+        let wild_twos = |cards| parse_hand(&format!("{cards} 0"), &rules).unwrap();
+        assert_eq!(wild_twos("22223").kind, HandType::FiveOfKind);
+        assert!(wild_twos("22223").key() < wild_twos("33332").key());
+        assert_eq!(wild_twos("2345A").kind, HandType::OnePair);
+    }
+
+    #[test]
+    fn decks_past_the_packed_key() {
+        // a separate joker card on top of the standard deck still packs
+        let rules = Custom::new("*23456789TJQKA", "*", 5).unwrap();
+        assert!(rules.packs());
+        let hands = ranked_hands("JKKK2 1\n*KKK2 2\nJJJJ* 3", &rules).unwrap();
+        let order = hands
+            .iter()
+            .map(|hand| (hand.bid, hand.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                (1, HandType::ThreeOfKind),
+                (2, HandType::FourOfKind),
+                (3, HandType::FiveOfKind)
+            ]
+        );
+
+        // 20 ranks and 20-card hands fall back to comparing kind and cards directly
+        let ranks = "abcdefghijklmnopqrst";
+        let rules = Custom::new(ranks, "a", 20).unwrap();
+        assert!(!rules.packs());
+        let labels = ranks.chars().collect::<Vec<_>>();
+        let mut rng = Rng::new(48);
+        let input = (0..500)
+            .map(|bid| {
+                let pool = (0..rng.range(1..12))
+                    .map(|_| *rng.pick(&labels))
+                    .collect::<Vec<_>>();
+                let cards = (0..20).map(|_| *rng.pick(&pool)).collect::<String>();
+                format!("{cards} {bid}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let hands = ranked_hands(&input, &rules).unwrap();
+        for hand in &hands {
+            assert_eq!(hand.kind, classify_hashmap(&hand.cards), "{hand:?}");
+        }
+        for pair in hands.windows(2) {
+            assert!((pair[0].kind, &pair[0].cards) <= (pair[1].kind, &pair[1].cards));
+        }
+    }
+
+    #[test]
+    fn rule_errors() {
+        let err = Custom::new("2344", "", 5).unwrap_err();
+        assert_eq!(err.to_string(), "card `4` ranked twice");
+        let err = Custom::new("234", "J", 5).unwrap_err();
+        assert_eq!(err.to_string(), "wildcard `J` is not a ranked card");
+        let err = Custom::new("234", "", 0).unwrap_err();
+        assert_eq!(err.to_string(), "hands need at least one card");
+
+        let err = parse_hands("32T3K 765\n32X3K 1", &Standard).unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: unknown card `X`");
+        let err = parse_hands("32T3 765", &Standard).unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 1: expected 5 cards, found 4");
+        let err = parse_hands("32T3K", &Standard).unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 1: expected cards and a bid");
+        let err = parse_hands("32T3K 765 1", &Standard).unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 1: expected cards and a bid");
+    }
+
+    #[test]
+    fn any_whitespace_between_fields() {
+        let hands = parse_hands("32T3K\t765\n  KK677   28  ", &Standard).unwrap();
+        let bids = hands.iter().map(|hand| hand.bid).collect::<Vec<_>>();
+        assert_eq!(bids, [765, 28]);
+    }
+
+    #[test]
+    fn explain_sample() {
+        let input = "
+32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483
+"
+        .trim();
+        assert_eq!(
+            explain_csv(input, &Jokers).unwrap(),
+            "\
+rank,hand,type,bid,winnings
+1,32T3K,one pair,765,765
+2,KK677,two pair,28,56
+3,T55J5,four of a kind,684,2052
+4,QQQJA,four of a kind,483,1932
+5,KTJJT,four of a kind,220,1100
+
+type,hands
+high card,0
+one pair,1
+two pair,1
+three of a kind,0
+full house,0
+four of a kind,3
+five of a kind,0
+"
+        );
+
+        let table = explain_table(input, &Standard).unwrap();
+        assert!(table.starts_with(
+            " rank  hand     type               bid  winnings
+    1  32T3K    one pair           765       765
+    2  KTJJT    two pair           220       440
+    3  KK677    two pair            28        84
+    4  T55J5    three of a kind    684      2736
+    5  QQQJA    three of a kind    483      2415
+"
+        ));
+        assert!(table.ends_with("\ntotal winnings: 6440\n"));
     }
 }
@@ -0,0 +1,311 @@
+use std::fmt;
+use std::ops::{Index, IndexMut, Range};
+use std::str::FromStr;
+
+use anyhow::bail;
+
+const NEIGHBORS4: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+const NEIGHBORS8: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pos {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Pos {
+    pub fn new(row: usize, col: usize) -> Self {
+        Self { row, col }
+    }
+}
+
+/// A rectangular grid stored row-major in a single `Vec`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    rows: usize,
+    cols: usize,
+}
+
+impl<T> Grid<T> {
+    pub fn new(rows: usize, cols: usize, fill: T) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            cells: vec![fill; rows * cols],
+            rows,
+            cols,
+        }
+    }
+
+    /// Parse one cell per char, one row per line. Both `\n` and `\r\n` line endings are
+    /// accepted, and every row must be the same width as the first.
+    pub fn parse_with(input: &str, mut f: impl FnMut(char) -> T) -> anyhow::Result<Self> {
+        let mut cells = Vec::with_capacity(input.len());
+        let mut rows = 0;
+        let mut cols = None;
+
+        for (row_num, line) in input.lines().enumerate() {
+            // `lines` leaves the `\r` on a final line with no `\n` after it
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let before = cells.len();
+            cells.extend(line.chars().map(&mut f));
+            let width = cells.len() - before;
+            match cols {
+                None => cols = Some(width),
+                Some(cols) if cols != width => {
+                    bail!("row {} has width {width}, expected {cols}", row_num + 1)
+                }
+                _ => {}
+            }
+            rows += 1;
+        }
+
+        Ok(Self {
+            cells,
+            rows,
+            cols: cols.unwrap_or(0),
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn in_bounds(&self, pos: Pos) -> bool {
+        pos.row < self.rows && pos.col < self.cols
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<&T> {
+        self.in_bounds(pos).then(|| &self.cells[self.idx(pos)])
+    }
+
+    pub fn get_mut(&mut self, pos: Pos) -> Option<&mut T> {
+        if self.in_bounds(pos) {
+            let idx = self.idx(pos);
+            Some(&mut self.cells[idx])
+        } else {
+            None
+        }
+    }
+
+    /// Step from `pos` by `(d_row, d_col)`, or `None` if that leaves the grid.
+    pub fn offset(&self, pos: Pos, d_row: isize, d_col: isize) -> Option<Pos> {
+        let row = pos.row.checked_add_signed(d_row)?;
+        let col = pos.col.checked_add_signed(d_col)?;
+        let pos = Pos::new(row, col);
+        self.in_bounds(pos).then_some(pos)
+    }
+
+    /// Orthogonal neighbours of `pos` that are inside the grid.
+    pub fn neighbors4(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        NEIGHBORS4
+            .iter()
+            .filter_map(move |&(d_row, d_col)| self.offset(pos, d_row, d_col))
+    }
+
+    /// Orthogonal and diagonal neighbours of `pos` that are inside the grid.
+    pub fn neighbors8(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        NEIGHBORS8
+            .iter()
+            .filter_map(move |&(d_row, d_col)| self.offset(pos, d_row, d_col))
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.cols..(row + 1) * self.cols]
+    }
+
+    pub fn iter_rows(&self) -> impl Iterator<Item = &[T]> {
+        // `chunks` panics on 0, and a grid with no columns has no cells anyway
+        self.cells.chunks(self.cols.max(1))
+    }
+
+    pub fn column(&self, col: usize) -> impl Iterator<Item = &T> {
+        assert!(col < self.cols, "column {col} out of bounds");
+        self.cells.iter().skip(col).step_by(self.cols)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Pos> {
+        let cols = self.cols;
+        (0..self.rows).flat_map(move |row| (0..cols).map(move |col| Pos::new(row, col)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Pos, &T)> {
+        self.positions().zip(self.cells.iter())
+    }
+
+    /// Cells inside `rows` × `cols`, clamped to the grid, in row-major order.
+    pub fn region(
+        &self,
+        rows: Range<usize>,
+        cols: Range<usize>,
+    ) -> impl Iterator<Item = (Pos, &T)> {
+        let rows = rows.start.min(self.rows)..rows.end.min(self.rows);
+        let cols = cols.start.min(self.cols)..cols.end.min(self.cols);
+        rows.flat_map(move |row| {
+            cols.clone()
+                .map(move |col| (Pos::new(row, col), &self.cells[row * self.cols + col]))
+        })
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            cells: self.cells.iter().map(f).collect(),
+            rows: self.rows,
+            cols: self.cols,
+        }
+    }
+
+    pub fn transpose(&self) -> Self
+    where
+        T: Clone,
+    {
+        self.remap(self.cols, self.rows, |pos| Pos::new(pos.col, pos.row))
+    }
+
+    /// Rotate a quarter turn clockwise.
+    pub fn rotate_cw(&self) -> Self
+    where
+        T: Clone,
+    {
+        self.remap(self.cols, self.rows, |pos| {
+            Pos::new(self.rows - 1 - pos.col, pos.row)
+        })
+    }
+
+    /// Rotate a quarter turn counter-clockwise.
+    pub fn rotate_ccw(&self) -> Self
+    where
+        T: Clone,
+    {
+        self.remap(self.cols, self.rows, |pos| {
+            Pos::new(pos.col, self.cols - 1 - pos.row)
+        })
+    }
+
+    // build a `rows` × `cols` grid where each new cell is copied from `src(new_pos)`
+    fn remap(&self, rows: usize, cols: usize, src: impl Fn(Pos) -> Pos) -> Self
+    where
+        T: Clone,
+    {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for row in 0..rows {
+            for col in 0..cols {
+                cells.push(self[src(Pos::new(row, col))].clone());
+            }
+        }
+        Self { cells, rows, cols }
+    }
+
+    fn idx(&self, pos: Pos) -> usize {
+        pos.row * self.cols + pos.col
+    }
+}
+
+impl FromStr for Grid<char> {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, |ch| ch)
+    }
+}
+
+impl<T> Index<Pos> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, pos: Pos) -> &Self::Output {
+        assert!(self.in_bounds(pos), "{pos:?} out of bounds");
+        &self.cells[self.idx(pos)]
+    }
+}
+
+impl<T> IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, pos: Pos) -> &mut Self::Output {
+        assert!(self.in_bounds(pos), "{pos:?} out of bounds");
+        let idx = self.idx(pos);
+        &mut self.cells[idx]
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (row_num, row) in self.iter_rows().enumerate() {
+            if row_num > 0 {
+                writeln!(f)?;
+            }
+            for cell in row {
+                write!(f, "{cell}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn grid() -> Grid<char> {
+        "abc\ndef".parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let grid = grid();
+        assert_eq!((grid.rows(), grid.cols()), (2, 3));
+        assert_eq!(grid[Pos::new(1, 2)], 'f');
+        assert_eq!(grid.get(Pos::new(2, 0)), None);
+        assert_eq!(grid.to_string(), "abc\ndef");
+
+        let crlf = "abc\r\ndef\r".parse::<Grid<char>>().unwrap();
+        assert_eq!(crlf, grid);
+
+        let err = "abc\nde".parse::<Grid<char>>().unwrap_err();
+        assert_eq!(err.to_string(), "row 2 has width 2, expected 3");
+    }
+
+    #[test]
+    fn neighbors() {
+        let grid = grid();
+        let corner = grid.neighbors8(Pos::new(0, 0)).collect::<Vec<_>>();
+        assert_eq!(corner, vec![Pos::new(0, 1), Pos::new(1, 0), Pos::new(1, 1)]);
+        assert_eq!(grid.neighbors8(Pos::new(0, 1)).count(), 5);
+        assert_eq!(grid.neighbors4(Pos::new(1, 1)).count(), 3);
+    }
+
+    #[test]
+    fn rows_columns_region() {
+        let grid = grid();
+        assert_eq!(grid.row(1), &['d', 'e', 'f']);
+        assert_eq!(grid.column(1).collect::<String>(), "be");
+        assert_eq!(grid.iter_rows().count(), 2);
+        let region = grid
+            .region(0..5, 1..3)
+            .map(|(_, ch)| ch)
+            .collect::<String>();
+        assert_eq!(region, "bcef");
+    }
+
+    #[test]
+    fn transpose_rotate() {
+        let grid = grid();
+        assert_eq!(grid.transpose().to_string(), "ad\nbe\ncf");
+        assert_eq!(grid.rotate_cw().to_string(), "da\neb\nfc");
+        assert_eq!(grid.rotate_ccw().to_string(), "cf\nbe\nad");
+        assert_eq!(grid.rotate_cw().rotate_ccw(), grid);
+    }
+}
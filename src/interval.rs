@@ -0,0 +1,323 @@
+use std::ops::{Neg, Range};
+
+/// A set of `usize`s stored as sorted, disjoint, non-adjacent, non-empty ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    rngs: Vec<Range<usize>>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.rngs
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Range<usize>> {
+        self.rngs.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rngs.is_empty()
+    }
+
+    /// How many numbers are in the set.
+    pub fn len(&self) -> usize {
+        self.rngs.iter().map(ExactSizeIterator::len).sum()
+    }
+
+    pub fn min(&self) -> Option<usize> {
+        self.rngs.first().map(|rng| rng.start)
+    }
+
+    pub fn contains(&self, num: usize) -> bool {
+        let idx = self.rngs.partition_point(|rng| rng.end <= num);
+        self.rngs.get(idx).is_some_and(|rng| rng.contains(&num))
+    }
+
+    pub fn insert(&mut self, rng: Range<usize>) {
+        self.rngs.push(rng);
+        normalize(&mut self.rngs);
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.iter().chain(other.iter()).cloned().collect()
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut rngs = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while let (Some(a), Some(b)) = (self.rngs.get(i), other.rngs.get(j)) {
+            let overlap = a.start.max(b.start)..a.end.min(b.end);
+            if !overlap.is_empty() {
+                rngs.push(overlap);
+            }
+            // drop whichever range finishes first; it can't overlap anything further on
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { rngs }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut rngs = Vec::new();
+        let mut j = 0;
+        for rng in self.iter() {
+            let mut start = rng.start;
+            // skip ranges of `other` entirely before this one
+            while other.rngs.get(j).is_some_and(|cut| cut.end <= start) {
+                j += 1;
+            }
+            let mut k = j;
+            while let Some(cut) = other.rngs.get(k).filter(|cut| cut.start < rng.end) {
+                if cut.start > start {
+                    rngs.push(start..cut.start);
+                }
+                start = start.max(cut.end);
+                k += 1;
+            }
+            if start < rng.end {
+                rngs.push(start..rng.end);
+            }
+        }
+        Self { rngs }
+    }
+
+    /// Split into the parts inside and outside `other`.
+    pub fn split(&self, other: &Self) -> (Self, Self) {
+        (self.intersection(other), self.difference(other))
+    }
+
+    /// Move every number by `offset`, or `None` if any would leave the `usize` range.
+    pub fn checked_shift(&self, offset: Offset) -> Option<Self> {
+        let rngs = self
+            .iter()
+            .map(|rng| Some(offset.apply(rng.start)?..offset.apply(rng.end)?))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { rngs })
+    }
+}
+
+/// How far to move numbers. The distance between two `usize`s doesn't always fit an `isize`,
+/// so the direction and size are kept apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Offset {
+    Up(usize),
+    Down(usize),
+}
+
+impl Offset {
+    // zero is always `Up(0)`, so equal offsets compare equal
+    pub const ZERO: Self = Self::Up(0);
+
+    /// The offset that moves `from` to `to`.
+    pub fn between(from: usize, to: usize) -> Self {
+        if to >= from {
+            Self::Up(to - from)
+        } else {
+            Self::Down(from - to)
+        }
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        match (self, other) {
+            (Self::Up(a), Self::Up(b)) => a.checked_add(b).map(Self::Up),
+            (Self::Down(a), Self::Down(b)) => a.checked_add(b).map(Self::Down),
+            (Self::Up(up), Self::Down(down)) | (Self::Down(down), Self::Up(up)) => {
+                Some(Self::between(down, up))
+            }
+        }
+    }
+
+    pub fn apply(self, num: usize) -> Option<usize> {
+        match self {
+            Self::Up(dist) => num.checked_add(dist),
+            Self::Down(dist) => num.checked_sub(dist),
+        }
+    }
+}
+
+impl Neg for Offset {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        match self {
+            Self::Up(0) => Self::ZERO,
+            Self::Up(dist) => Self::Down(dist),
+            Self::Down(dist) => Self::Up(dist),
+        }
+    }
+}
+
+impl From<Range<usize>> for IntervalSet {
+    fn from(rng: Range<usize>) -> Self {
+        Self::from_iter([rng])
+    }
+}
+
+impl FromIterator<Range<usize>> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = Range<usize>>>(iter: I) -> Self {
+        let mut rngs = iter.into_iter().collect();
+        normalize(&mut rngs);
+        Self { rngs }
+    }
+}
+
+// sort, drop empty ranges and merge any that overlap or touch
+fn normalize(rngs: &mut Vec<Range<usize>>) {
+    rngs.retain(|rng| !rng.is_empty());
+    rngs.sort_unstable_by_key(|rng| rng.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(rngs.len());
+    for rng in rngs.drain(..) {
+        match merged.last_mut() {
+            Some(last) if rng.start <= last.end => last.end = last.end.max(rng.end),
+            _ => merged.push(rng),
+        }
+    }
+    *rngs = merged;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rng::Rng;
+
+    const UNIVERSE: usize = 64;
+
+    fn random_set(rng: &mut Rng) -> IntervalSet {
+        let count = rng.range(0..6);
+        (0..count)
+            .map(|_| {
+                let start = rng.range(0..UNIVERSE as u64) as usize;
+                let len = rng.range(0..12) as usize;
+                start..(start + len).min(UNIVERSE)
+            })
+            .collect()
+    }
+
+    // the reference model: which numbers of the universe are in the set
+    fn members(set: &IntervalSet) -> Vec<bool> {
+        (0..UNIVERSE).map(|num| set.contains(num)).collect()
+    }
+
+    fn brute(set: &IntervalSet) -> Vec<bool> {
+        (0..UNIVERSE)
+            .map(|num| set.iter().any(|rng| rng.contains(&num)))
+            .collect()
+    }
+
+    fn assert_normalized(set: &IntervalSet) {
+        for rng in set.iter() {
+            assert!(!rng.is_empty(), "{set:?}");
+        }
+        for pair in set.ranges().windows(2) {
+            assert!(pair[0].end < pair[1].start, "{set:?}");
+        }
+    }
+
+    // run `check` on many random pairs of sets
+    fn for_random_pairs(check: impl Fn(&IntervalSet, &IntervalSet)) {
+        let mut rng = Rng::new(38);
+        for _ in 0..500 {
+            let a = random_set(&mut rng);
+            let b = random_set(&mut rng);
+            check(&a, &b);
+        }
+    }
+
+    #[test]
+    fn normalize_merges() {
+        let set = IntervalSet::from_iter([5..8, 0..2, 2..3, 7..10, 4..4]);
+        assert_eq!(set.ranges(), &[0..3, 5..10]);
+        assert_eq!(set.len(), 8);
+        assert_eq!(set.min(), Some(0));
+    }
+
+    #[test]
+    fn normalized_and_contains() {
+        for_random_pairs(|a, _| {
+            assert_normalized(a);
+            assert_eq!(members(a), brute(a));
+            assert_eq!(a.len(), members(a).into_iter().filter(|&m| m).count());
+        });
+    }
+
+    #[test]
+    fn set_operations() {
+        for_random_pairs(|a, b| {
+            let (ma, mb) = (members(a), members(b));
+            let expect = |op: fn(bool, bool) -> bool| {
+                ma.iter()
+                    .zip(&mb)
+                    .map(|(&x, &y)| op(x, y))
+                    .collect::<Vec<_>>()
+            };
+
+            for (set, op) in [
+                (a.union(b), (|x, y| x || y) as fn(bool, bool) -> bool),
+                (a.intersection(b), |x, y| x && y),
+                (a.difference(b), |x, y| x && !y),
+            ] {
+                assert_normalized(&set);
+                assert_eq!(members(&set), expect(op), "{a:?} {b:?} -> {set:?}");
+            }
+        });
+    }
+
+    #[test]
+    fn split_partitions() {
+        for_random_pairs(|a, b| {
+            let (inside, outside) = a.split(b);
+            assert!(inside.intersection(&outside).is_empty());
+            assert_eq!(inside.union(&outside), *a);
+            assert_eq!(inside.difference(b), IntervalSet::new());
+        });
+    }
+
+    #[test]
+    fn shift() {
+        for_random_pairs(|a, _| {
+            let shifted = a.checked_shift(Offset::Up(100)).unwrap();
+            assert_normalized(&shifted);
+            assert_eq!(shifted.len(), a.len());
+            assert_eq!(shifted.checked_shift(Offset::Down(100)).unwrap(), *a);
+        });
+
+        let set = IntervalSet::from(3..5);
+        assert_eq!(set.checked_shift(Offset::Down(4)), None);
+        assert_eq!(
+            set.checked_shift(Offset::Down(3)),
+            Some(IntervalSet::from(0..2))
+        );
+
+        // a move of more than `isize::MAX`
+        let max = usize::MAX;
+        let set = IntervalSet::from(0..2);
+        let shifted = set.checked_shift(Offset::between(0, max - 2)).unwrap();
+        assert_eq!(shifted, IntervalSet::from(max - 2..max));
+        assert_eq!(shifted.checked_shift(Offset::Down(max - 2)), Some(set));
+        assert_eq!(shifted.checked_shift(Offset::Up(1)), None);
+    }
+
+    #[test]
+    fn offset_arithmetic() {
+        let max = usize::MAX;
+        assert_eq!(Offset::between(max, 0), Offset::Down(max));
+        assert_eq!(
+            (-Offset::Up(3)).checked_add(Offset::Up(3)),
+            Some(Offset::ZERO)
+        );
+        assert_eq!(
+            Offset::Up(2).checked_add(Offset::Down(5)),
+            Some(Offset::Down(3))
+        );
+        assert_eq!(Offset::Up(max).checked_add(Offset::Up(1)), None);
+        assert_eq!(Offset::Down(1).apply(0), None);
+    }
+}
@@ -1,3 +1,7 @@
+pub mod grid;
+pub mod interval;
+pub mod rng;
+
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
@@ -9,7 +9,7 @@ fn main() -> anyhow::Result<()> {
         exit(1);
     }
 
-    let day_str = env::args().last().unwrap();
+    let day_str = env::args().next_back().unwrap();
 
     if day_str.parse::<usize>().is_err() {
         eprintln!("expected day number to be a positive int");
@@ -31,7 +31,6 @@ fn main() -> anyhow::Result<()> {
     bin_file.write_all(bin.as_bytes())?;
 
     let mut cargo_toml = std::fs::OpenOptions::new()
-        .write(true)
         .append(true)
         .open("Cargo.toml")?;
     cargo_toml.write_all(bin_info.as_bytes())?;
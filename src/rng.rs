@@ -0,0 +1,30 @@
+use std::ops::Range;
+
+/// Small deterministic xorshift generator for building synthetic inputs.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at 0
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// Uniform-ish value in `rng`. Modulo bias is fine for test data.
+    pub fn range(&mut self, rng: Range<u64>) -> u64 {
+        rng.start + self.next_u64() % (rng.end - rng.start)
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0..items.len() as u64) as usize]
+    }
+}